@@ -0,0 +1,29 @@
+//! Layer the settings file below environment variables with `figment`.
+//!
+//! ```sh
+//! BARAPP_BAR=42 cargo run --example figment --features figment
+//! ```
+
+use figment::{
+    providers::{Env, Serialized},
+    Figment,
+};
+use serde::{Deserialize, Serialize};
+use settings::SettingsProvider;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Config {
+    foo: String,
+    bar: u32,
+}
+
+fn main() -> Result<(), Box<figment::Error>> {
+    let config: Config = Figment::new()
+        .merge(Serialized::defaults(Config::default()))
+        .merge(SettingsProvider::new("com", "Foo-Corp", "Bar-App").required(false))
+        .merge(Env::prefixed("BARAPP_").ignore(&["CONFIG_PATH"]))
+        .extract()?;
+
+    println!("{:#?}", config);
+    Ok(())
+}
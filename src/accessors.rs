@@ -0,0 +1,77 @@
+//! Generate typed accessors for the fields of the settings.
+
+/// Generate a trait with a getter and a setter for each listed field and
+/// implement it for [`Settings`](crate::Settings).
+///
+/// Each field is given as `field, setter: Type;`. The getter is named after
+/// the field and returns a reference, the setter replaces the value. Since
+/// the implementation accesses the fields directly, a misspelled field or a
+/// wrong type fails to compile, unlike dotted keys such as `"network.port"`.
+/// Nested fields can be reached by listing a struct field and chaining
+/// further accessors on its type.
+///
+/// ```rust
+/// # use settings::{settings_accessors, Settings};
+/// # use tempfile::tempdir;
+/// # use std::{error::Error, fs};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Clone, Deserialize, Serialize)]
+/// struct Config {
+///     pub name: String,
+///     pub port: u16,
+/// }
+///
+/// settings_accessors! {
+///     /// Typed access to [`Config`].
+///     pub trait ConfigAccessors for Config {
+///         name, set_name: String;
+///         port, set_port: u16;
+///     }
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.ron");
+/// fs::write(&path, r#"(name: "server", port: 8080)"#)?;
+/// let mut settings = Settings::<Config>::load_from(&path)?;
+/// assert_eq!(settings.port(), &8080);
+///
+/// settings.set_port(9090);
+/// settings.save()?;
+/// assert_eq!(Settings::<Config>::load_from(&path)?.port(), &9090);
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! settings_accessors {
+    (
+        $(#[$attr:meta])*
+        $vis:vis trait $name:ident for $ty:ty {
+            $($field:ident, $setter:ident: $field_ty:ty;)*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis trait $name {
+            $(
+                #[doc = concat!("Get `", stringify!($field), "`.")]
+                fn $field(&self) -> &$field_ty;
+
+                #[doc = concat!("Set `", stringify!($field), "` to `value`.")]
+                fn $setter(&mut self, value: $field_ty);
+            )*
+        }
+
+        impl $name for $crate::Settings<$ty> {
+            $(
+                fn $field(&self) -> &$field_ty {
+                    &::std::convert::AsRef::<$ty>::as_ref(self).$field
+                }
+
+                fn $setter(&mut self, value: $field_ty) {
+                    ::std::convert::AsMut::<$ty>::as_mut(self).$field = value;
+                }
+            )*
+        }
+    };
+}
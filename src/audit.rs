@@ -0,0 +1,80 @@
+//! Append a record of every change to an audit log.
+
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::{ser, Codec};
+
+/// The old and new compact RON of a changed field, `None` if absent.
+type Changes = BTreeMap<String, (Option<String>, Option<String>)>;
+
+/// Append the difference between `previous`, the old contents of the file at
+/// `path`, and `current` to the audit log at `log`.
+///
+/// Nothing is written if no field changed. Failures are logged and otherwise
+/// ignored, so they never prevent the settings from being saved.
+pub(crate) fn record<T>(
+    log: &Path,
+    path: &Path,
+    codec: &dyn Codec<T>,
+    previous: Option<Vec<u8>>,
+    current: &T,
+) where
+    T: Serialize,
+{
+    let previous = previous
+        .and_then(|bytes| codec.deserialize(&bytes).ok())
+        .and_then(|previous| ser::fields(&previous).ok())
+        .unwrap_or_default();
+    let result = ser::fields(current).and_then(|current| {
+        let changes = diff(previous, current);
+        if changes.is_empty() {
+            return Ok(None);
+        }
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        Ok(Some(format!(
+            "(time: {}, file: {}, changes: {})\n",
+            time,
+            ron::to_string(path)?,
+            ron::to_string(&changes)?
+        )))
+    });
+
+    let appended = match result {
+        Ok(Some(line)) => OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|err| err.to_string()),
+        Ok(None) => Ok(()),
+        Err(err) => Err(err.to_string()),
+    };
+    if let Err(err) = appended {
+        warn!("Could not append to audit log {:?}: {}", log, err);
+    }
+}
+
+/// The fields that differ between `previous` and `current`.
+fn diff(previous: Vec<(String, String)>, current: Vec<(String, String)>) -> Changes {
+    let mut changes: Changes = previous
+        .into_iter()
+        .map(|(field, old)| (field, (Some(old), None)))
+        .collect();
+    for (field, new) in current {
+        changes.entry(field).or_default().1 = Some(new);
+    }
+    changes.retain(|_, (old, new)| old != new);
+    changes
+}
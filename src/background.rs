@@ -0,0 +1,117 @@
+//! Save settings on a background thread.
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Error, Settings};
+
+/// Background saves shared by clones of the same settings, by target path.
+#[derive(Debug, Default)]
+pub(crate) struct Pending {
+    slots: Mutex<HashMap<PathBuf, Arc<Slot>>>,
+}
+
+/// Background saves to a single path.
+#[derive(Debug, Default)]
+struct Slot {
+    /// Held while a background save writes.
+    write: Mutex<()>,
+
+    /// The number of the most recently requested save.
+    latest: AtomicU64,
+}
+
+impl Pending {
+    /// The background saves to `path`.
+    fn slot(&self, path: &Path) -> Arc<Slot> {
+        self.slots
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(path.to_path_buf())
+            .or_default()
+            .clone()
+    }
+}
+
+impl<T> Settings<T>
+where
+    T: Debug + Clone + Serialize + DeserializeOwned + Send + 'static,
+{
+    /// Save the settings on a background thread, e.g. to keep a UI
+    /// responsive.
+    ///
+    /// The settings are cloned right away, so later changes are not picked
+    /// up, and then written by [`save`](Self::save) on a new thread. Saves
+    /// coalesce: background saves of these settings and their clones to the
+    /// same path run one at a time, and a save that has not started writing
+    /// when a newer one to the same path is requested is dropped, so only the
+    /// latest value is written. The handle of a dropped save yields
+    /// [`Error::Superseded`]. Other errors are only delivered through the
+    /// handle as well.
+    ///
+    /// ```rust
+    /// # use settings::{Error, Settings};
+    /// # use tempfile::tempdir;
+    /// # use std::error;
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub counter: u32,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// let mut settings = Settings::new(Config { counter: 0 }, &path);
+    ///
+    /// let mut handles = Vec::new();
+    /// for counter in 1..=100 {
+    ///     settings.counter = counter;
+    ///     handles.push(settings.save_in_background());
+    /// }
+    /// let last = handles.pop().unwrap();
+    /// last.join().unwrap()?;
+    /// for handle in handles {
+    ///     match handle.join().unwrap() {
+    ///         Ok(()) | Err(Error::Superseded) => {}
+    ///         Err(err) => return Err(err.into()),
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(Settings::<Config>::load_from(&path)?.counter, 100);
+    ///
+    /// let other = dir.path().join("other.ron");
+    /// let copy = settings.clone().with_path(&other);
+    /// let first = settings.save_in_background();
+    /// let second = copy.save_in_background();
+    /// first.join().unwrap()?;
+    /// second.join().unwrap()?;
+    /// assert_eq!(Settings::<Config>::load_from(&other)?.counter, 100);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn save_in_background(&self) -> JoinHandle<Result<(), Error>> {
+        let settings = self.clone();
+        let slot = self.pending.slot(&self.path);
+        let id = slot.latest.fetch_add(1, Ordering::SeqCst) + 1;
+        thread::spawn(move || {
+            let _write = slot
+                .write
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if slot.latest.load(Ordering::SeqCst) != id {
+                return Err(Error::Superseded);
+            }
+            settings.save()
+        })
+    }
+}
@@ -0,0 +1,1073 @@
+//! Configure how settings are loaded.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use log::{debug, warn};
+use ron::ser::PrettyConfig;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    backups, confine, find_all, read, read_limited, repair, trace::Span, value, Codec, Error,
+    Layer, Ron, Settings,
+};
+
+/// What [`Builder::load`] does when more than one settings file exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictStrategy {
+    /// Use the first existing file in search order.
+    #[default]
+    FirstWins,
+
+    /// Use the last existing file in search order.
+    LastWins,
+
+    /// Merge all existing files.
+    ///
+    /// Files earlier in search order take precedence, field by field, over
+    /// later ones, so a `settings.ron` in the current directory can override
+    /// single fields of the one in the configuration directory. The merged
+    /// settings are saved to the first file.
+    Merge,
+}
+
+/// A builder for [`Settings`], created by [`Settings::builder`].
+///
+/// ```rust
+/// # use settings::{ConflictStrategy, Settings};
+/// # use tempfile::tempdir;
+/// # use std::{env, error::Error, fs};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Debug, Clone, Deserialize, Serialize)]
+/// struct Config {
+///     pub foo: String,
+///     pub bar: u32,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// # env::set_current_dir(&dir)?;
+/// let env_path = dir.path().join("env.ron");
+/// fs::write(&env_path, r#"(foo: "env", bar: 1)"#)?;
+/// env::set_var("MYAPP_CONFIG_PATH", &env_path);
+/// fs::write("settings.ron", r#"(foo: "cwd", bar: 2)"#)?;
+///
+/// let first = Settings::<Config>::builder()
+///     .conflict_strategy(ConflictStrategy::FirstWins)
+///     .load("com", "Foo-Corp", "MyApp")?;
+/// assert_eq!((first.foo.as_str(), first.bar), ("env", 1));
+///
+/// let last = Settings::<Config>::builder()
+///     .conflict_strategy(ConflictStrategy::LastWins)
+///     .load("com", "Foo-Corp", "MyApp")?;
+/// assert_eq!((last.foo.as_str(), last.bar), ("cwd", 2));
+///
+/// fs::write(&env_path, r#"(foo: "env")"#)?;
+/// let merged = Settings::<Config>::builder()
+///     .conflict_strategy(ConflictStrategy::Merge)
+///     .load("com", "Foo-Corp", "MyApp")?;
+/// assert_eq!((merged.foo.as_str(), merged.bar), ("env", 2));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Builder<T> {
+    conflict_strategy: ConflictStrategy,
+    codec: Option<Arc<dyn Codec<T>>>,
+    pretty: PrettyConfig,
+    pretty_below_bytes: Option<usize>,
+    header_comment: Option<String>,
+    lenient_parse: bool,
+    coerce_scalars: bool,
+    struct_names_optional: bool,
+    validate_path: bool,
+    audit_log: Option<PathBuf>,
+    recover_on_corrupt: bool,
+    base_dir: Option<PathBuf>,
+    max_file_size: Option<usize>,
+}
+
+impl<T> Builder<T>
+where
+    T: Debug + Clone + Serialize + DeserializeOwned,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            conflict_strategy: ConflictStrategy::default(),
+            codec: None,
+            pretty: PrettyConfig::default(),
+            pretty_below_bytes: None,
+            header_comment: None,
+            lenient_parse: false,
+            coerce_scalars: false,
+            struct_names_optional: false,
+            validate_path: false,
+            audit_log: None,
+            recover_on_corrupt: false,
+            base_dir: None,
+            max_file_size: None,
+        }
+    }
+
+    /// Set the format the settings are stored in. Defaults to [`Ron`].
+    ///
+    /// The pretty printing options of this builder only apply to the default
+    /// codec.
+    pub fn codec<C>(mut self, codec: C) -> Self
+    where
+        C: Codec<T> + 'static,
+    {
+        self.codec = Some(Arc::new(codec));
+        self
+    }
+
+    /// Set whether structs are saved with their names. Defaults to `false`.
+    ///
+    /// RON always writes enum variants in their short form, without the name
+    /// of the enum, so this only affects structs. Both forms are accepted
+    /// when loading.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// enum Theme {
+    ///     Light,
+    ///     Dark,
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Window {
+    ///     pub width: u32,
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub theme: Theme,
+    ///     pub window: Window,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, "(theme: Dark, window: (width: 800))")?;
+    ///
+    /// Settings::<Config>::builder()
+    ///     .struct_names(false)
+    ///     .load_from(&path)?
+    ///     .save()?;
+    /// assert_eq!(fs::read_to_string(&path)?, "(
+    ///     theme: Dark,
+    ///     window: (
+    ///         width: 800,
+    ///     ),
+    /// )");
+    ///
+    /// Settings::<Config>::builder()
+    ///     .struct_names(true)
+    ///     .load_from(&path)?
+    ///     .save()?;
+    /// assert_eq!(fs::read_to_string(&path)?, "Config(
+    ///     theme: Dark,
+    ///     window: Window(
+    ///         width: 800,
+    ///     ),
+    /// )");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn struct_names(mut self, struct_names: bool) -> Self {
+        self.pretty = self.pretty.struct_names(struct_names);
+        self
+    }
+
+    /// Set whether arrays are saved on a single line. Defaults to `false`.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub list: Vec<u32>,
+    /// #     pub pair: (u32, u32),
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, "(list: [1, 2], pair: (3, 4))")?;
+    ///
+    /// Settings::<Config>::builder()
+    ///     .compact_arrays(true)
+    ///     .load_from(&path)?
+    ///     .save()?;
+    /// assert_eq!(fs::read_to_string(&path)?, "(
+    ///     list: [1, 2],
+    ///     pair: (3, 4),
+    /// )");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compact_arrays(mut self, compact_arrays: bool) -> Self {
+        self.pretty = self.pretty.compact_arrays(compact_arrays);
+        self
+    }
+
+    /// Set whether the members of tuples are saved on separate lines.
+    /// Defaults to `false`.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub list: Vec<u32>,
+    /// #     pub pair: (u32, u32),
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, "(list: [1, 2], pair: (3, 4))")?;
+    ///
+    /// Settings::<Config>::builder()
+    ///     .separate_tuple_members(true)
+    ///     .load_from(&path)?
+    ///     .save()?;
+    /// assert_eq!(fs::read_to_string(&path)?, "(
+    ///     list: [
+    ///         1,
+    ///         2,
+    ///     ],
+    ///     pair: (
+    ///         3,
+    ///         4,
+    ///     ),
+    /// )");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn separate_tuple_members(mut self, separate_tuple_members: bool) -> Self {
+        self.pretty = self.pretty.separate_tuple_members(separate_tuple_members);
+        self
+    }
+
+    /// Set whether array elements are saved with a comment holding their
+    /// index. Defaults to `false`.
+    ///
+    /// The comments are ignored when loading.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub list: Vec<u32>,
+    /// #     pub pair: (u32, u32),
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, "(list: [1, 2], pair: (3, 4))")?;
+    ///
+    /// Settings::<Config>::builder()
+    ///     .enumerate_arrays(true)
+    ///     .load_from(&path)?
+    ///     .save()?;
+    /// assert_eq!(fs::read_to_string(&path)?, "(
+    ///     list: [
+    ///         /*[0]*/ 1,
+    ///         /*[1]*/ 2,
+    ///     ],
+    ///     pair: (3, 4),
+    /// )");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn enumerate_arrays(mut self, enumerate_arrays: bool) -> Self {
+        self.pretty = self.pretty.enumerate_arrays(enumerate_arrays);
+        self
+    }
+
+    /// Pretty-print only small settings and write large ones compactly.
+    ///
+    /// The size is estimated by serializing the settings compactly first: if
+    /// that takes at least `bytes` bytes, the compact form is saved, without
+    /// struct names or any other pretty printing option. Otherwise the
+    /// settings are serialized again, this time pretty-printed, so small
+    /// files stay easy to edit while large ones stay small. By default the
+    /// settings are always pretty-printed.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub list: Vec<u32>,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// // `(list:[1,2])` takes 12 bytes.
+    /// fs::write(&path, "(list: [1, 2])")?;
+    ///
+    /// Settings::<Config>::builder()
+    ///     .pretty_below_bytes(13)
+    ///     .load_from(&path)?
+    ///     .save()?;
+    /// assert_eq!(fs::read_to_string(&path)?, "(\n    list: [\n        1,\n        2,\n    ],\n)");
+    ///
+    /// Settings::<Config>::builder()
+    ///     .pretty_below_bytes(12)
+    ///     .load_from(&path)?
+    ///     .save()?;
+    /// assert_eq!(fs::read_to_string(&path)?, "(list:[1,2])");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pretty_below_bytes(mut self, bytes: usize) -> Self {
+        self.pretty_below_bytes = Some(bytes);
+        self
+    }
+
+    /// Write `header` as a comment at the top of the settings file.
+    ///
+    /// Each line of `header` is prefixed with `// `, so the file still loads.
+    /// Useful to mark generated files. By default no header is written.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "Hello World")"#)?;
+    ///
+    /// Settings::<Config>::builder()
+    ///     .header_comment("Generated by MyApp 2.1.0\nEdit with care".to_string())
+    ///     .load_from(&path)?
+    ///     .save()?;
+    /// assert_eq!(fs::read_to_string(&path)?, "// Generated by MyApp 2.1.0
+    /// // Edit with care
+    /// (
+    ///     foo: \"Hello World\",
+    /// )");
+    /// assert_eq!(Settings::<Config>::load_from(&path)?.foo, "Hello World");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn header_comment(mut self, header: String) -> Self {
+        self.header_comment = Some(header);
+        self
+    }
+
+    /// Set what [`load`](Self::load) does when more than one settings file
+    /// exists. Defaults to [`ConflictStrategy::FirstWins`].
+    pub fn conflict_strategy(mut self, conflict_strategy: ConflictStrategy) -> Self {
+        self.conflict_strategy = conflict_strategy;
+        self
+    }
+
+    /// Set whether to repair misplaced commas when the file fails to parse.
+    ///
+    /// When enabled and the file cannot be deserialized, repeated commas,
+    /// commas directly after an opening bracket and a comma after the root
+    /// value are removed and parsing is retried once. A warning is logged if
+    /// the repair succeeded; otherwise the original error is returned. The
+    /// file itself is not changed. Defaults to `false`.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// #     pub bar: u32,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "a,,b",, bar: 1,,),"#)?;
+    /// assert!(Settings::<Config>::load_from(&path).is_err());
+    ///
+    /// let settings = Settings::<Config>::builder()
+    ///     .lenient_parse(true)
+    ///     .load_from(&path)?;
+    /// assert_eq!(settings.foo, "a,,b");
+    /// assert_eq!(settings.bar, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn lenient_parse(mut self, lenient_parse: bool) -> Self {
+        self.lenient_parse = lenient_parse;
+        self
+    }
+
+    /// Set whether to read numbers and booleans stored as strings, as older
+    /// versions of an application may have written them.
+    ///
+    /// When enabled and the file cannot be deserialized, it is parsed into an
+    /// untyped value and deserialized field by field: whenever the failing
+    /// field holds a string with a number or a boolean, like `"8080"` or
+    /// `"true"`, the string is replaced with that scalar and deserializing is
+    /// retried. String fields are left alone, even if they look like numbers.
+    /// Each coercion is logged as a warning. If a field fails that cannot be
+    /// coerced, the original error is returned. The file itself is not
+    /// changed.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// #     pub port: u16,
+    /// #     pub verbose: bool,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "Hello World", port: "8080", verbose: "true")"#)?;
+    /// assert!(Settings::<Config>::load_from(&path).is_err());
+    ///
+    /// let settings = Settings::<Config>::builder()
+    ///     .coerce_scalars(true)
+    ///     .load_from(&path)?;
+    /// assert_eq!(settings.port, 8080);
+    /// assert!(settings.verbose);
+    ///
+    /// fs::write(&path, r#"(foo: "2", port: "8080", verbose: "yes")"#)?;
+    /// let builder = Settings::<Config>::builder().coerce_scalars(true);
+    /// assert!(builder.clone().load_from(&path).is_err());
+    ///
+    /// fs::write(&path, r#"(foo: "2", port: "8080", verbose: false)"#)?;
+    /// let settings = builder.load_from(&path)?;
+    /// assert_eq!((settings.foo.as_str(), settings.port), ("2", 8080));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn coerce_scalars(mut self, coerce_scalars: bool) -> Self {
+        self.coerce_scalars = coerce_scalars;
+        self
+    }
+
+    /// Set whether struct names that do not match `T` are ignored when
+    /// loading. Defaults to `false`.
+    ///
+    /// A struct may always be written with or without its name, as in
+    /// `Config(foo: "")` or `(foo: "")`, but RON rejects a name that differs
+    /// from the Rust type, e.g. in files written by other tools. When
+    /// enabled, such names are removed and parsing is retried. A warning is
+    /// logged if this succeeded; the file itself is not changed.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// let builder = Settings::<Config>::builder().struct_names_optional(true);
+    ///
+    /// fs::write(&path, r#"Config(foo: "named")"#)?;
+    /// assert_eq!(builder.clone().load_from(&path)?.foo, "named");
+    ///
+    /// fs::write(&path, r#"(foo: "unnamed")"#)?;
+    /// assert_eq!(builder.clone().load_from(&path)?.foo, "unnamed");
+    ///
+    /// fs::write(&path, r#"AppConfig(foo: "renamed")"#)?;
+    /// assert!(Settings::<Config>::load_from(&path).is_err());
+    /// assert_eq!(builder.load_from(&path)?.foo, "renamed");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn struct_names_optional(mut self, struct_names_optional: bool) -> Self {
+        self.struct_names_optional = struct_names_optional;
+        self
+    }
+
+    /// Set whether to validate the settings path while loading.
+    ///
+    /// When enabled, the path of the loaded file is canonicalized and its
+    /// parent directory is checked to be a writable directory, failing with
+    /// [`Error::InvalidPath`] otherwise. Writability is checked by creating
+    /// and removing a temporary file in the directory, so ownership and
+    /// access control lists are taken into account. This moves failures from the first
+    /// [`save`](Settings::save) to startup, where they are easier to handle.
+    /// Defaults to `false`.
+    ///
+    /// ```rust
+    /// # use settings::{Error, Settings};
+    /// # use tempfile::tempdir;
+    /// # use std::{error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "")"#)?;
+    ///
+    /// let settings = Settings::<Config>::builder()
+    ///     .validate_path(true)
+    ///     .load_from(dir.path().join(".").join("settings.ron"))?;
+    /// assert_eq!(settings.path(), path.canonicalize()?);
+    ///
+    /// # #[cfg(unix)]
+    /// # {
+    /// # use std::os::unix::fs::PermissionsExt;
+    /// fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o555))?;
+    /// # // Permissions do not apply to root.
+    /// # if fs::File::create(dir.path().join("probe")).is_err() {
+    /// let result = Settings::<Config>::builder()
+    ///     .validate_path(true)
+    ///     .load_from(&path);
+    /// assert!(matches!(result, Err(Error::InvalidPath { .. })));
+    /// # }
+    /// # fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o755))?;
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn validate_path(mut self, validate_path: bool) -> Self {
+        self.validate_path = validate_path;
+        self
+    }
+
+    /// Set whether to fall back to a backup when the file is corrupt.
+    ///
+    /// When enabled and the file cannot be deserialized, the backups made by
+    /// [`Settings::backup`] are tried from newest to oldest, and the first one
+    /// that loads is used instead. A warning is logged in that case; if no
+    /// backup loads, the original error is returned. The settings are still
+    /// saved to the corrupt file, so the next save repairs it. Merged files
+    /// are not recovered. Defaults to `false`.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "backed up")"#)?;
+    /// Settings::<Config>::load_from(&path)?.backup()?;
+    /// fs::write(&path, r#"(foo: "#)?;
+    ///
+    /// assert!(Settings::<Config>::load_from(&path).is_err());
+    /// let settings = Settings::<Config>::builder()
+    ///     .recover_on_corrupt(true)
+    ///     .load_from(&path)?;
+    /// assert_eq!(settings.foo, "backed up");
+    /// assert_eq!(settings.path(), path);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn recover_on_corrupt(mut self, recover_on_corrupt: bool) -> Self {
+        self.recover_on_corrupt = recover_on_corrupt;
+        self
+    }
+
+    /// Record every change made to a settings file in the file at `path`.
+    ///
+    /// Every method that saves the settings, like [`save`](Settings::save),
+    /// [`save_to`](Settings::save_to), [`save_minimal`](Settings::save_minimal)
+    /// or [`modify_field_locked`](Settings::modify_field_locked), records its
+    /// writes, including those to mirrors. Each write that changes at least
+    /// one top-level field appends a line of
+    /// RON of the form `(time: 1760000000, file: "settings.ron", changes:
+    /// {"bar": (Some("1"), Some("2"))})`, holding the seconds since the Unix
+    /// epoch, the saved file, and the old and new value of every changed
+    /// field as compact RON. A failure to append is logged as a warning and
+    /// does not fail the save.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// #     pub bar: u32,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "Hello World", bar: 1)"#)?;
+    /// let log = dir.path().join("audit.log");
+    ///
+    /// let mut settings = Settings::<Config>::builder()
+    ///     .audit_log(&log)
+    ///     .load_from(&path)?;
+    /// settings.bar = 2;
+    /// settings.save()?;
+    /// settings.save()?;
+    /// settings.foo = "Goodbye".to_string();
+    /// settings.save_to(&path)?;
+    ///
+    /// let entries = fs::read_to_string(&log)?;
+    /// assert_eq!(entries.lines().count(), 2);
+    /// assert!(entries.contains(r#"changes: {"bar":(Some("1"),Some("2"))}"#));
+    /// assert!(entries.contains(r#"changes: {"foo":(Some("\"Hello World\""),Some("\"Goodbye\""))}"#));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn audit_log<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.audit_log = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Confine the settings to files inside `base`, e.g. for untrusted
+    /// plugin configs.
+    ///
+    /// Relative paths passed to [`load_from`](Self::load_from) and
+    /// [`Settings::save_to`] are taken relative to `base`. Every path is
+    /// canonicalized before it is used, resolving `..` and symlinks, and
+    /// loading or saving fails with [`Error::PathEscape`] if it leads outside
+    /// of `base`. This covers every file the settings write, read back or lock,
+    /// including mirrors, backups, every file [merged](ConflictStrategy::Merge)
+    /// into the settings, [`Settings::export_fields`] and paths set
+    /// with [`Settings::set_path`]; only [`Settings::write_temp`] writes to
+    /// the system's temporary directory regardless. Paths through a `..`
+    /// below a directory that does not exist are rejected as well. By default
+    /// any path is allowed.
+    ///
+    /// ```rust
+    /// # use settings::{Error, Settings};
+    /// # use tempfile::tempdir;
+    /// # use std::{error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn error::Error>> {
+    /// # let dir = tempdir()?;
+    /// let base = dir.path().join("plugins").join("greeter");
+    /// fs::create_dir_all(&base)?;
+    /// fs::write(base.join("settings.ron"), r#"(foo: "Hello World")"#)?;
+    /// fs::write(dir.path().join("secret.ron"), r#"(foo: "secret")"#)?;
+    ///
+    /// let settings = Settings::<Config>::builder()
+    ///     .base_dir(&base)
+    ///     .load_from("settings.ron")?;
+    /// assert_eq!(settings.foo, "Hello World");
+    /// assert_eq!(settings.path(), base.canonicalize()?.join("settings.ron"));
+    /// settings.save_to("backup.ron")?;
+    /// assert!(base.join("backup.ron").exists());
+    ///
+    /// let result = Settings::<Config>::builder()
+    ///     .base_dir(&base)
+    ///     .load_from("../../secret.ron");
+    /// assert!(matches!(result, Err(Error::PathEscape { .. })));
+    /// let result = settings.save_to("../../../../etc/passwd");
+    /// assert!(matches!(result, Err(Error::PathEscape { .. })));
+    /// let result = settings.save_to(dir.path().join("secret.ron"));
+    /// assert!(matches!(result, Err(Error::PathEscape { .. })));
+    ///
+    /// let settings = settings.with_path(dir.path().join("secret.ron"));
+    /// assert!(matches!(settings.save(), Err(Error::PathEscape { .. })));
+    /// assert!(matches!(settings.save_minimal(), Err(Error::PathEscape { .. })));
+    /// assert!(matches!(settings.backup(), Err(Error::PathEscape { .. })));
+    /// let result = settings.export_fields(dir.path().join("public.ron"), &["foo"]);
+    /// assert!(matches!(result, Err(Error::PathEscape { .. })));
+    /// assert_eq!(Settings::<Config>::load_from(dir.path().join("secret.ron"))?.foo, "secret");
+    /// assert!(!dir.path().join("public.ron").exists());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn base_dir<P>(mut self, base: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.base_dir = Some(base.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set the largest settings file, in bytes, that is read when loading.
+    ///
+    /// A larger file fails with [`Error::TooLarge`] before it is read into
+    /// memory, which guards against maliciously huge files. Backups tried by
+    /// [`recover_on_corrupt`](Self::recover_on_corrupt) and every file
+    /// [merged](ConflictStrategy::Merge) into the settings are checked too. By
+    /// default there is no limit.
+    ///
+    /// ```rust
+    /// # use settings::{Error, Settings};
+    /// # use tempfile::tempdir;
+    /// # use std::{error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, format!(r#"(foo: "{}")"#, "a".repeat(1000)))?;
+    ///
+    /// let result = Settings::<Config>::builder()
+    ///     .max_file_size(64)
+    ///     .load_from(&path);
+    /// assert!(matches!(result, Err(Error::TooLarge { size: 1009, limit: 64 })));
+    ///
+    /// let settings = Settings::<Config>::builder()
+    ///     .max_file_size(4096)
+    ///     .load_from(&path)?;
+    /// assert_eq!(settings.foo.len(), 1000);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn max_file_size(mut self, bytes: usize) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Open the settings file given a qualifier, organization, and application name.
+    ///
+    /// See [`Settings::load`] for the locations that are searched.
+    pub fn load(
+        self,
+        qualifier: &str,
+        organization: &str,
+        application: &str,
+    ) -> Result<Settings<T>, Error> {
+        let (project_dirs, paths) = find_all(qualifier, organization, application)?;
+
+        let mut settings = match self.conflict_strategy {
+            _ if paths.is_empty() => return Err(Error::NotFound),
+            ConflictStrategy::FirstWins => self.load_from(&paths[0])?,
+            ConflictStrategy::LastWins => self.load_from(&paths[paths.len() - 1])?,
+            ConflictStrategy::Merge if paths.len() == 1 => self.load_from(&paths[0])?,
+            ConflictStrategy::Merge => self.load_merged(paths[0].clone(), &paths)?,
+        };
+        settings.project_dirs = Some(project_dirs);
+        Ok(settings)
+    }
+
+    /// Load the settings file from the given path.
+    pub fn load_from<P>(self, path: P) -> Result<Settings<T>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = self.confined(path.as_ref())?;
+        let inner = match self.read(&path) {
+            Err(err @ Error::Deserialize { .. }) if self.recover_on_corrupt => {
+                self.recover(&path).ok_or(err)?
+            }
+            result => result?,
+        };
+        self.build(path, inner)
+    }
+
+    /// Load the settings from `reader`, saving them to `path_for_saves`.
+    ///
+    /// The whole reader is consumed before parsing. Empty input is treated
+    /// like an empty settings file.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::error::Error;
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// let path = dir.path().join("settings.ron");
+    /// let input = r#"(foo: "piped")"#.as_bytes();
+    ///
+    /// let settings = Settings::<Config>::builder().load_from_reader(input, path.clone())?;
+    /// assert_eq!(settings.foo, "piped");
+    /// assert_eq!(settings.path(), path);
+    ///
+    /// assert!(Settings::<Config>::builder()
+    ///     .load_from_reader("".as_bytes(), path)
+    ///     .is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_from_reader<R>(
+        self,
+        mut reader: R,
+        path_for_saves: PathBuf,
+    ) -> Result<Settings<T>, Error>
+    where
+        R: Read,
+    {
+        debug!("Loading settings from a reader for {:?}", path_for_saves);
+        let inner = {
+            let span = Span::load(&path_for_saves, self.resolve_codec().format());
+
+            let mut bytes = Vec::new();
+            reader
+                .read_to_end(&mut bytes)
+                .map_err(|source| Error::Open {
+                    source,
+                    path: path_for_saves.clone(),
+                })?;
+            span.record_bytes(bytes.len());
+            self.parse(&path_for_saves, &bytes)?
+        };
+        self.build(path_for_saves, inner)
+    }
+
+    /// Load the settings from standard input, saving them to `path_for_saves`.
+    ///
+    /// This lets scripts pipe the settings in, as in `cat settings.ron | myapp`.
+    /// See [`load_from_reader`](Self::load_from_reader).
+    pub fn load_from_stdin(self, path_for_saves: PathBuf) -> Result<Settings<T>, Error> {
+        self.load_from_reader(io::stdin().lock(), path_for_saves)
+    }
+
+    /// Read and deserialize the file at `path`.
+    fn read(&self, path: &Path) -> Result<T, Error> {
+        debug!("Loading settings from {:?}", path);
+        let span = Span::load(path, self.resolve_codec().format());
+
+        let bytes = self.read_bytes(path)?;
+        span.record_bytes(bytes.len());
+        self.parse(path, &bytes)
+    }
+
+    /// Read the whole file at `path`, up to the [limit](Self::max_file_size).
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        match self.max_file_size {
+            Some(limit) => read_limited(path, limit),
+            None => read(path),
+        }
+    }
+
+    /// Load the newest backup of `path` that can be read.
+    fn recover(&self, path: &Path) -> Option<T> {
+        backups(path).into_iter().find_map(|backup| {
+            let inner = self.read(&backup).ok()?;
+            warn!(
+                "Settings file {:?} is corrupt, loaded backup {:?}",
+                path, backup
+            );
+            Some(inner)
+        })
+    }
+
+    /// Deserialize `bytes`, read from `path`, repairing them if configured.
+    fn parse(&self, path: &Path, bytes: &[u8]) -> Result<T, Error> {
+        match self.repair(path, bytes) {
+            Err(err @ Error::Deserialize { .. }) if self.coerce_scalars => {
+                self.coerce(path, bytes).ok_or(err)
+            }
+            result => result,
+        }
+    }
+
+    /// Deserialize `bytes`, repairing misplaced commas if enabled.
+    fn repair(&self, path: &Path, bytes: &[u8]) -> Result<T, Error> {
+        match self.deserialize(path, bytes) {
+            Err(err @ Error::Deserialize { .. }) if self.lenient_parse => {
+                let repaired = std::str::from_utf8(bytes).ok().and_then(repair::commas);
+                match repaired.map(|repaired| self.deserialize(path, repaired.as_bytes())) {
+                    Some(Ok(inner)) => {
+                        warn!("Repaired misplaced commas in settings file {:?}", path);
+                        Ok(inner)
+                    }
+                    _ => Err(err),
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Deserialize `bytes` after replacing strings that hold scalars.
+    fn coerce(&self, path: &Path, bytes: &[u8]) -> Option<T> {
+        let mut value = value::from_file(self.resolve_codec().as_ref(), bytes).ok()?;
+        let mut coerced = false;
+        loop {
+            let err = match serde_path_to_error::deserialize(value.clone()) {
+                Ok(inner) => return coerced.then_some(inner),
+                Err(err) => err,
+            };
+            let field = value::get_segments_mut(&mut value, err.path())?;
+            if !value::coerce_scalar(field) {
+                return None;
+            }
+            coerced = true;
+            warn!(
+                "Coerced string at `{}` in settings file {:?}",
+                err.path(),
+                path
+            );
+        }
+    }
+
+    /// Deserialize `bytes`, removing mismatched struct names if enabled.
+    fn deserialize(&self, path: &Path, bytes: &[u8]) -> Result<T, Error> {
+        let codec = self.resolve_codec();
+        let mut repaired: Option<String> = None;
+        loop {
+            let input = repaired
+                .as_ref()
+                .map_or(bytes, |repaired| repaired.as_bytes());
+            let err = match codec.deserialize(input) {
+                Ok(inner) => {
+                    if repaired.is_some() {
+                        warn!(
+                            "Ignored mismatched struct names in settings file {:?}",
+                            path
+                        );
+                    }
+                    return Ok(inner);
+                }
+                Err(err) if !self.struct_names_optional => return Err(err),
+                Err(err) => err,
+            };
+            let stripped = match &err {
+                Error::Deserialize {
+                    source:
+                        ron::de::SpannedError {
+                            code: ron::Error::ExpectedDifferentStructName { found, .. },
+                            position,
+                        },
+                    ..
+                } => std::str::from_utf8(input)
+                    .ok()
+                    .and_then(|input| repair::struct_name(input, *position, found)),
+                _ => None,
+            };
+            repaired = Some(stripped.ok_or(err)?);
+        }
+    }
+
+    /// Merge `layers`, the first taking precedence, and save to `path`.
+    pub(crate) fn load_merged(
+        self,
+        path: PathBuf,
+        layers: &[PathBuf],
+    ) -> Result<Settings<T>, Error> {
+        let codec = self.resolve_codec();
+        let mut merged = None;
+        let mut provenance = BTreeMap::new();
+        for layer in layers.iter().rev() {
+            let layer = self.confined(layer)?;
+            debug!("Merging settings from {:?}", layer);
+
+            let value = value::from_file(codec.as_ref(), &self.read_bytes(&layer)?)?;
+            for key in value::keys(&value) {
+                provenance.insert(key, Layer::File(layer.clone()));
+            }
+            match &mut merged {
+                None => merged = Some(value),
+                Some(merged) => value::merge(merged, value),
+            }
+        }
+
+        let inner: T = merged
+            .ok_or(Error::NotFound)?
+            .into_rust()
+            .map_err(Error::Value)?;
+        let mut settings = self.build(path, inner)?;
+        settings.provenance = provenance;
+        Ok(settings)
+    }
+
+    /// Resolve `path` inside the [base directory](Self::base_dir), if any.
+    fn confined(&self, path: &Path) -> Result<PathBuf, Error> {
+        match &self.base_dir {
+            Some(base) => confine(base, path),
+            None => Ok(path.to_path_buf()),
+        }
+    }
+
+    /// Create the settings using the configured options.
+    fn build(self, path: PathBuf, inner: T) -> Result<Settings<T>, Error> {
+        let path = self.confined(&path)?;
+        let path = if self.validate_path {
+            validate(&path)?
+        } else {
+            path
+        };
+
+        let mut settings = Settings::from_parts(path, inner);
+        settings.codec = self.resolve_codec();
+        settings.audit_log = self.audit_log;
+        settings.base_dir = self.base_dir;
+        Ok(settings)
+    }
+
+    /// The configured codec, or [`Ron`] using the pretty printing options.
+    fn resolve_codec(&self) -> Arc<dyn Codec<T>> {
+        match &self.codec {
+            Some(codec) => codec.clone(),
+            None => {
+                let mut ron = Ron::with_pretty_config(self.pretty.clone());
+                if let Some(bytes) = self.pretty_below_bytes {
+                    ron = ron.pretty_below_bytes(bytes);
+                }
+                if let Some(header) = &self.header_comment {
+                    ron = ron.header_comment(header.clone());
+                }
+                Arc::new(ron)
+            }
+        }
+    }
+}
+
+/// Canonicalize `path` and check that its parent directory is writable.
+fn validate(path: &Path) -> Result<PathBuf, Error> {
+    let invalid = |source| Error::InvalidPath {
+        source,
+        path: path.to_path_buf(),
+    };
+
+    let path = path.canonicalize().map_err(invalid)?;
+    let parent = path.parent().unwrap_or(&path);
+    let metadata = fs::metadata(parent).map_err(invalid)?;
+    if !metadata.is_dir() {
+        return Err(invalid(io::Error::other("parent is not a directory")));
+    }
+    tempfile::NamedTempFile::new_in(parent).map_err(invalid)?;
+    Ok(path)
+}
@@ -0,0 +1,255 @@
+//! Formats the settings file can be stored in.
+
+use std::fmt::Debug;
+
+use ron::ser::PrettyConfig;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Error, Partial};
+
+/// Converts settings to and from the bytes stored in the settings file.
+///
+/// [`Ron`] is used unless a different codec is passed to
+/// [`Builder::codec`](crate::Builder::codec). The codec is used whenever the
+/// settings are loaded, saved or reloaded. Methods that operate on untyped
+/// values, like [`Settings::modify_field_locked`](crate::Settings::modify_field_locked),
+/// parse RON files themselves and read other files through
+/// [`deserialize_value`](Self::deserialize_value), failing with
+/// [`Error::Codec`] for codecs that do not implement it.
+///
+/// ```rust
+/// # use settings::{Codec, Error, Settings};
+/// # use tempfile::tempdir;
+/// # use std::{error, fs};
+/// /// Stores a single number as decimal text.
+/// #[derive(Debug)]
+/// struct Decimal;
+///
+/// impl Codec<u32> for Decimal {
+///     fn deserialize(&self, bytes: &[u8]) -> Result<u32, Error> {
+///         std::str::from_utf8(bytes)
+///             .ok()
+///             .and_then(|text| text.trim().parse().ok())
+///             .ok_or_else(|| Error::Codec("expected a decimal number".into()))
+///     }
+///
+///     fn serialize(&self, value: &u32) -> Result<Vec<u8>, Error> {
+///         Ok(value.to_string().into_bytes())
+///     }
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn error::Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("counter.txt");
+/// fs::write(&path, "41")?;
+///
+/// let mut settings = Settings::with_codec(Decimal).load_from(&path)?;
+/// *settings += 1;
+/// settings.save()?;
+///
+/// assert_eq!(fs::read_to_string(&path)?, "42");
+///
+/// let result = settings.modify_field_locked("", |_| {});
+/// assert!(matches!(result, Err(Error::Codec(_))));
+/// # Ok(())
+/// # }
+/// ```
+pub trait Codec<T>: Debug + Send + Sync {
+    /// Parse the contents of a settings file.
+    fn deserialize(&self, bytes: &[u8]) -> Result<T, Error>;
+
+    /// Parse the contents of a settings file into an untyped value.
+    ///
+    /// Unit enum variants should be returned as strings and other variants as
+    /// maps with a single entry from the variant name to its content, the way
+    /// self-describing formats like JSON store them. By default this fails
+    /// with [`Error::Codec`], since not every format maps onto
+    /// [`ron::Value`].
+    fn deserialize_value(&self, bytes: &[u8]) -> Result<ron::Value, Error> {
+        let _ = bytes;
+        Err(Error::Codec(
+            format!("{} files cannot be read as untyped values", self.format()).into(),
+        ))
+    }
+
+    /// Produce the contents of a settings file.
+    fn serialize(&self, value: &T) -> Result<Vec<u8>, Error>;
+
+    /// Produce the contents of a file holding only some of the top-level
+    /// fields of the settings.
+    ///
+    /// Used by [`Settings::save_minimal`](crate::Settings::save_minimal) and
+    /// [`Settings::export_fields`](crate::Settings::export_fields). By default
+    /// this fails with [`Error::Codec`], since writing a [`Partial`] needs a
+    /// serde serializer.
+    fn serialize_partial(&self, value: &Partial<'_, T>) -> Result<Vec<u8>, Error> {
+        let _ = value;
+        Err(Error::Codec(
+            format!(
+                "{} files cannot hold a subset of the settings",
+                self.format()
+            )
+            .into(),
+        ))
+    }
+
+    /// Produce the contents of a settings file that replaces `previous`.
+    ///
+    /// Codecs that can keep comments and formatting of the existing file
+    /// override this; by default it is the same as
+    /// [`serialize`](Self::serialize).
+    fn update(&self, previous: &[u8], value: &T) -> Result<Vec<u8>, Error> {
+        let _ = previous;
+        self.serialize(value)
+    }
+
+    /// A short name for the format, used in diagnostics.
+    fn format(&self) -> &'static str {
+        "custom"
+    }
+}
+
+/// The default codec, storing settings as pretty-printed RON.
+///
+/// When a file fails to deserialize, the error names the field that failed
+/// in addition to the position in the file.
+///
+/// ```rust
+/// # use settings::Settings;
+/// # use tempfile::tempdir;
+/// # use std::{error::Error, fs};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Debug, Clone, Deserialize, Serialize)]
+/// struct Endpoint {
+///     pub port: u16,
+/// }
+///
+/// #[derive(Debug, Clone, Deserialize, Serialize)]
+/// struct Network {
+///     pub endpoints: Vec<Endpoint>,
+/// }
+///
+/// #[derive(Debug, Clone, Deserialize, Serialize)]
+/// struct Config {
+///     pub network: Network,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.ron");
+/// fs::write(&path, r#"(network: (endpoints: [(port: 80), (port: 443), (port: "8080")]))"#)?;
+///
+/// let err = Settings::<Config>::load_from(&path).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "Could not deserialize settings file at `network.endpoints[2].port`"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Ron {
+    pretty: PrettyConfig,
+    pretty_below_bytes: Option<usize>,
+    header_comment: Option<String>,
+}
+
+impl Ron {
+    /// Create a RON codec using the given pretty printing options.
+    pub fn with_pretty_config(pretty: PrettyConfig) -> Self {
+        Self {
+            pretty,
+            pretty_below_bytes: None,
+            header_comment: None,
+        }
+    }
+
+    /// Only pretty-print settings that are smaller than `bytes` when written
+    /// compactly, and write larger ones compactly.
+    ///
+    /// See [`Builder::pretty_below_bytes`](crate::Builder::pretty_below_bytes).
+    pub fn pretty_below_bytes(mut self, bytes: usize) -> Self {
+        self.pretty_below_bytes = Some(bytes);
+        self
+    }
+
+    /// Write `header` as a comment above the settings.
+    ///
+    /// See [`Builder::header_comment`](crate::Builder::header_comment).
+    pub fn header_comment(mut self, header: String) -> Self {
+        self.header_comment = Some(header);
+        self
+    }
+
+    /// Serialize `value` with the configured pretty printing and header.
+    fn to_bytes<V>(&self, value: &V) -> Result<Vec<u8>, Error>
+    where
+        V: Serialize + ?Sized,
+    {
+        if let Some(bytes) = self.pretty_below_bytes {
+            let compact = ron::to_string(value).map_err(Error::Serialize)?;
+            if compact.len() >= bytes {
+                return Ok(self.with_header(compact));
+            }
+        }
+
+        ron::ser::to_string_pretty(value, self.pretty.clone())
+            .map(|contents| self.with_header(contents))
+            .map_err(Error::Serialize)
+    }
+
+    /// Prepend the header comment, if any, to `contents`.
+    fn with_header(&self, contents: String) -> Vec<u8> {
+        let Some(header) = &self.header_comment else {
+            return contents.into_bytes();
+        };
+
+        let mut output = String::with_capacity(header.len() + contents.len());
+        for line in header.lines() {
+            output.push_str("//");
+            if !line.is_empty() {
+                output.push(' ');
+                output.push_str(line);
+            }
+            output.push('\n');
+        }
+        output.push_str(&contents);
+        output.into_bytes()
+    }
+}
+
+impl<T> Codec<T> for Ron
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn deserialize(&self, bytes: &[u8]) -> Result<T, Error> {
+        let mut deserializer = ron::Deserializer::from_bytes(bytes)?;
+        let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|err| {
+            let path = err.path().to_string();
+            Error::Deserialize {
+                source: deserializer.span_error(err.into_inner()),
+                path: (path != ".").then_some(path),
+            }
+        })?;
+        deserializer
+            .end()
+            .map_err(|err| deserializer.span_error(err))?;
+        Ok(value)
+    }
+
+    fn deserialize_value(&self, bytes: &[u8]) -> Result<ron::Value, Error> {
+        Ok(ron::de::from_bytes(bytes)?)
+    }
+
+    fn serialize(&self, value: &T) -> Result<Vec<u8>, Error> {
+        self.to_bytes(value)
+    }
+
+    fn serialize_partial(&self, value: &Partial<'_, T>) -> Result<Vec<u8>, Error> {
+        self.to_bytes(value)
+    }
+
+    fn format(&self) -> &'static str {
+        "ron"
+    }
+}
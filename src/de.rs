@@ -0,0 +1,564 @@
+//! Deserializers that only look at part of a settings file, and one for
+//! untyped [`Value`]s.
+
+use std::{fmt, marker::PhantomData};
+
+use serde::de::{
+    self,
+    value::{MapDeserializer, SeqDeserializer},
+    DeserializeOwned, DeserializeSeed, Deserializer, EnumAccess, IgnoredAny, IntoDeserializer,
+    MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
+};
+
+use crate::value::{Number, Value};
+
+/// Deserialize the value stored under a single top-level key, or under a
+/// dotted path of map keys and sequence indices.
+///
+/// Every other value is skipped without being deserialized. Yields `None` if
+/// the key is absent.
+pub(crate) struct Section<'k, S> {
+    key: &'k str,
+    rest: Option<&'k str>,
+    _marker: PhantomData<S>,
+}
+
+impl<'k, S> Section<'k, S> {
+    pub(crate) fn new(key: &'k str) -> Self {
+        Self {
+            key,
+            rest: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Follow the dotted `path`, e.g. `firewall.rules.0`.
+    pub(crate) fn path(path: &'k str) -> Self {
+        let (key, rest) = match path.split_once('.') {
+            Some((key, rest)) => (key, Some(rest)),
+            None => (path, None),
+        };
+        Self {
+            key,
+            rest,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Deserialize the value found under the key, or descend into it.
+    fn found<'de, D>(&self, deserializer: D) -> Result<Option<S>, D::Error>
+    where
+        S: DeserializeOwned,
+        D: Deserializer<'de>,
+    {
+        match self.rest {
+            Some(rest) => Section::path(rest).deserialize(deserializer),
+            None => S::deserialize(deserializer).map(Some),
+        }
+    }
+}
+
+impl<'de, S> DeserializeSeed<'de> for Section<'_, S>
+where
+    S: DeserializeOwned,
+{
+    type Value = Option<S>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, S> Visitor<'de> for Section<'_, S>
+where
+    S: DeserializeOwned,
+{
+    type Value = Option<S>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a struct, map or sequence")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut section = None;
+        while let Some(key) = map.next_key::<ron::Value>()? {
+            if section.is_none() && key == ron::Value::String(self.key.to_string()) {
+                section = map.next_value_seed(Found(&self))?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(section)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let Ok(index) = self.key.parse::<usize>() else {
+            while seq.next_element::<IgnoredAny>()?.is_some() {}
+            return Ok(None);
+        };
+        for _ in 0..index {
+            if seq.next_element::<IgnoredAny>()?.is_none() {
+                return Ok(None);
+            }
+        }
+        let section = seq.next_element_seed(Found(&self))?.flatten();
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(section)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+/// The value under the key a [`Section`] is looking for.
+struct Found<'s, 'k, S>(&'s Section<'k, S>);
+
+impl<'de, S> DeserializeSeed<'de> for Found<'_, '_, S>
+where
+    S: DeserializeOwned,
+{
+    type Value = Option<S>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.0.found(deserializer)
+    }
+}
+
+/// Pass each element of the sequence stored under a single top-level key to
+/// a callback, one at a time.
+///
+/// Every other value is skipped without being deserialized. Yields `None` if
+/// the key is absent.
+pub(crate) struct ArrayField<'k, I, F> {
+    key: &'k str,
+    f: F,
+    _marker: PhantomData<I>,
+}
+
+impl<'k, I, F> ArrayField<'k, I, F> {
+    pub(crate) fn new(key: &'k str, f: F) -> Self {
+        Self {
+            key,
+            f,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, I, F> DeserializeSeed<'de> for ArrayField<'_, I, F>
+where
+    I: DeserializeOwned,
+    F: FnMut(I),
+{
+    type Value = Option<()>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, I, F> Visitor<'de> for ArrayField<'_, I, F>
+where
+    I: DeserializeOwned,
+    F: FnMut(I),
+{
+    type Value = Option<()>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a struct or map")
+    }
+
+    fn visit_map<A>(mut self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut found = None;
+        while let Some(key) = map.next_key::<ron::Value>()? {
+            if found.is_none() && key == ron::Value::String(self.key.to_string()) {
+                found = Some(map.next_value_seed(Elements {
+                    f: &mut self.f,
+                    _marker: PhantomData,
+                })?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// Pass each element of a sequence to `f`.
+struct Elements<'f, I, F> {
+    f: &'f mut F,
+    _marker: PhantomData<I>,
+}
+
+impl<'de, I, F> DeserializeSeed<'de> for Elements<'_, I, F>
+where
+    I: DeserializeOwned,
+    F: FnMut(I),
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, I, F> Visitor<'de> for Elements<'_, I, F>
+where
+    I: DeserializeOwned,
+    F: FnMut(I),
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while let Some(element) = seq.next_element()? {
+            (self.f)(element);
+        }
+        Ok(())
+    }
+}
+
+/// Reads enums and struct names back from an untyped [`Value`].
+///
+/// Enums are read from [`Value::Named`], as parsed from RON, and from a
+/// string or a map with a single entry, which is how other formats write
+/// them.
+impl<'de> Deserializer<'de> for Value {
+    type Error = ron::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Unit => visitor.visit_unit(),
+            Value::Bool(bool) => visitor.visit_bool(bool),
+            Value::Number(Number::I64(integer)) => visitor.visit_i64(integer),
+            Value::Number(Number::U64(integer)) => visitor.visit_u64(integer),
+            Value::Number(Number::F64(float)) => visitor.visit_f64(float),
+            Value::Char(char) => visitor.visit_char(char),
+            Value::String(string) => visitor.visit_string(string),
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(value)) => visitor.visit_some(*value),
+            Value::Seq(seq) | Value::Tuple(seq) => visit_seq(seq, visitor),
+            Value::Map(map) => visit_map(map, visitor),
+            Value::Named(name, content) => match *content {
+                Value::Unit => visitor.visit_string(name),
+                content => visit_map(vec![(Value::String(name), content)], visitor),
+            },
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Option(None) | Value::Unit => visitor.visit_none(),
+            Value::Option(Some(value)) => visitor.visit_some(*value),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Tuple(tuple) if tuple.is_empty() => visitor.visit_unit(),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.unnamed(name).deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.unnamed(name) {
+            Value::Tuple(mut tuple) if tuple.len() == 1 => {
+                visitor.visit_newtype_struct(tuple.remove(0))
+            }
+            value => visitor.visit_newtype_struct(value),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Unit => visit_seq(Vec::new(), visitor),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.unnamed(name).deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Named(_, content) if matches!(*content, Value::Map(_)) => {
+                content.deserialize_any(visitor)
+            }
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let value = match self {
+            // A struct name written as a map with a single entry, like enums
+            // in other formats.
+            Value::Map(mut map)
+                if map.len() == 1
+                    && map[0].0 == Value::String(name.to_string())
+                    && !fields.contains(&name) =>
+            {
+                map.remove(0).1
+            }
+            value => value.unnamed(name),
+        };
+        match value {
+            Value::Unit => visit_map(Vec::new(), visitor),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let (variant, content, parenthesized) = match self {
+            Value::Named(variant, content) => (variant, *content, true),
+            Value::String(variant) => (variant, Value::Unit, true),
+            Value::Map(mut map) if map.len() == 1 => match map.remove(0) {
+                (Value::String(variant), content) => (variant, content, false),
+                (key, _) => return Err(de::Error::invalid_type(key.unexpected(), &"a variant")),
+            },
+            value => return Err(de::Error::invalid_type(value.unexpected(), &"an enum")),
+        };
+        visitor.visit_enum(Enum {
+            variant,
+            content: VariantContent {
+                content,
+                parenthesized,
+            },
+        })
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf identifier
+    }
+}
+
+impl IntoDeserializer<'_, ron::Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl Value {
+    /// Strip the name of the struct `name`, if it was written with it.
+    fn unnamed(self, name: &str) -> Value {
+        match self {
+            Value::Named(found, content) if found == name => *content,
+            value => value,
+        }
+    }
+
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            Value::Unit => Unexpected::Unit,
+            Value::Bool(bool) => Unexpected::Bool(*bool),
+            Value::Number(Number::I64(integer)) => Unexpected::Signed(*integer),
+            Value::Number(Number::U64(integer)) => Unexpected::Unsigned(*integer),
+            Value::Number(Number::F64(float)) => Unexpected::Float(*float),
+            Value::Char(char) => Unexpected::Char(*char),
+            Value::String(string) => Unexpected::Str(string),
+            Value::Option(_) => Unexpected::Option,
+            Value::Seq(_) | Value::Tuple(_) => Unexpected::Seq,
+            Value::Map(_) => Unexpected::Map,
+            Value::Named(..) => Unexpected::Enum,
+        }
+    }
+}
+
+fn visit_seq<'de, V>(seq: Vec<Value>, visitor: V) -> Result<V::Value, ron::Error>
+where
+    V: Visitor<'de>,
+{
+    let mut deserializer = SeqDeserializer::new(seq.into_iter());
+    let value = visitor.visit_seq(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+fn visit_map<'de, V>(map: Vec<(Value, Value)>, visitor: V) -> Result<V::Value, ron::Error>
+where
+    V: Visitor<'de>,
+{
+    let mut deserializer = MapDeserializer::new(map.into_iter());
+    let value = visitor.visit_map(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// An enum variant read from a [`Value`].
+struct Enum {
+    variant: String,
+    content: VariantContent,
+}
+
+/// The content of an enum variant.
+struct VariantContent {
+    content: Value,
+
+    /// Whether `content` is what followed the variant in parentheses, so a
+    /// newtype is wrapped in a [`Value::Tuple`].
+    parenthesized: bool,
+}
+
+impl<'de> EnumAccess<'de> for Enum {
+    type Error = ron::Error;
+    type Variant = VariantContent;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(Value::String(self.variant))?;
+        Ok((variant, self.content))
+    }
+}
+
+impl<'de> VariantAccess<'de> for VariantContent {
+    type Error = ron::Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self.content {
+            Value::Unit => Ok(()),
+            Value::Tuple(tuple) if tuple.is_empty() => Ok(()),
+            value => Err(de::Error::invalid_type(
+                value.unexpected(),
+                &"a unit variant",
+            )),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.content {
+            Value::Tuple(mut tuple) if self.parenthesized && tuple.len() == 1 => {
+                seed.deserialize(tuple.remove(0))
+            }
+            value => seed.deserialize(value),
+        }
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.content.deserialize_tuple(len, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.content.deserialize_map(visitor)
+    }
+}
@@ -0,0 +1,101 @@
+//! Compare settings files field by field.
+
+use std::path::Path;
+
+use ron::Value;
+
+use crate::{open, value, Error};
+
+/// A difference between two settings files, found by [`diff_files`].
+///
+/// `path` is the dotted path of the field, e.g. `network.port`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldDiff {
+    /// The field only exists in the second file.
+    Added { path: String, value: Value },
+
+    /// The field only exists in the first file.
+    Removed { path: String, value: Value },
+
+    /// The field has a different value, possibly of a different type, in the
+    /// second file.
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+/// Compare the settings files at `a` and `b`.
+///
+/// Both files are parsed into [`ron::Value`]s. Structs and maps are compared
+/// key by key, recursing into nested ones; any other value, including
+/// sequences, is compared as a whole. Within each map, the fields of `a` come
+/// first, sorted by key, followed by those added in `b`.
+///
+/// ```rust
+/// # use settings::{diff_files, FieldDiff};
+/// # use tempfile::tempdir;
+/// # use std::{error::Error, fs};
+/// use ron::Value;
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// let (a, b) = (dir.path().join("a.ron"), dir.path().join("b.ron"));
+/// fs::write(&a, r#"(name: "demo", network: (port: 80, host: "localhost"))"#)?;
+/// fs::write(&b, r#"(name: "demo", network: (port: "80", proxy: true))"#)?;
+///
+/// let diffs = diff_files(&a, &b)?;
+/// assert_eq!(diffs, vec![
+///     FieldDiff::Removed { path: "network.host".to_string(), value: Value::String("localhost".to_string()) },
+///     FieldDiff::Changed {
+///         path: "network.port".to_string(),
+///         old: Value::Number(80.into()),
+///         new: Value::String("80".to_string()),
+///     },
+///     FieldDiff::Added { path: "network.proxy".to_string(), value: Value::Bool(true) },
+/// ]);
+/// # Ok(())
+/// # }
+/// ```
+pub fn diff_files(a: &Path, b: &Path) -> Result<Vec<FieldDiff>, Error> {
+    let a: Value = ron::de::from_reader(open(a)?).map_err(Error::from)?;
+    let b: Value = ron::de::from_reader(open(b)?).map_err(Error::from)?;
+
+    let mut diffs = Vec::new();
+    diff("", a, b, &mut diffs);
+    Ok(diffs)
+}
+
+/// Collect the differences between `a` and `b`, found at `path`.
+fn diff(path: &str, a: Value, b: Value, diffs: &mut Vec<FieldDiff>) {
+    let join = |key: &Value| {
+        let segment = value::key_name(&key.clone().into());
+        if path.is_empty() {
+            segment
+        } else {
+            format!("{}.{}", path, segment)
+        }
+    };
+    match (a, b) {
+        (Value::Map(a), Value::Map(mut b)) => {
+            for (key, old) in a {
+                let path = join(&key);
+                match b.remove(&key) {
+                    Some(new) => diff(&path, old, new, diffs),
+                    None => diffs.push(FieldDiff::Removed { path, value: old }),
+                }
+            }
+            for (key, value) in b {
+                let path = join(&key);
+                diffs.push(FieldDiff::Added { path, value });
+            }
+        }
+        (old, new) if old != new => diffs.push(FieldDiff::Changed {
+            path: path.to_string(),
+            old,
+            new,
+        }),
+        _ => {}
+    }
+}
@@ -0,0 +1,88 @@
+//! Normalize the settings right before they are written.
+
+use std::{borrow::Cow, fmt, sync::Arc};
+
+use crate::Settings;
+
+/// A function applied to the settings before they are saved.
+pub(crate) struct PreSaveHook<T>(Arc<dyn Fn(&mut T) + Send + Sync>);
+
+impl<T> Clone for PreSaveHook<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> fmt::Debug for PreSaveHook<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PreSaveHook")
+    }
+}
+
+impl<T> Settings<T>
+where
+    T: Clone,
+{
+    /// Run `f` on the settings every time they are saved, e.g. to trim
+    /// strings, sort lists or clamp values.
+    ///
+    /// Every method that writes the settings, like [`save`](Self::save),
+    /// [`save_to`](Self::save_to) or [`save_minimal`](Self::save_minimal),
+    /// applies `f` to a clone of the settings and writes that clone. Saving
+    /// only borrows the settings, so the in-memory value is left as it is;
+    /// call [`normalize`](Self::normalize) to apply `f` to it as well.
+    /// Setting a new hook replaces the previous one.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// let mut settings = Settings::new(Config { foo: "  Hello World \n".to_string() }, &path);
+    /// settings.set_pre_save_hook(|config: &mut Config| {
+    ///     config.foo = config.foo.trim().to_string();
+    /// });
+    /// settings.save()?;
+    ///
+    /// assert_eq!(Settings::<Config>::load_from(&path)?.foo, "Hello World");
+    /// assert_eq!(settings.foo, "  Hello World \n");
+    ///
+    /// settings.normalize();
+    /// assert_eq!(settings.foo, "Hello World");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_pre_save_hook<F>(&mut self, f: F)
+    where
+        F: Fn(&mut T) + Send + Sync + 'static,
+    {
+        self.pre_save_hook = Some(PreSaveHook(Arc::new(f)));
+    }
+
+    /// Apply the [pre-save hook](Self::set_pre_save_hook), if any, to the
+    /// in-memory settings, so they match what the next save writes.
+    pub fn normalize(&mut self) {
+        if let Some(PreSaveHook(hook)) = &self.pre_save_hook {
+            hook(&mut self.inner);
+        }
+    }
+
+    /// The settings as they are written, with the pre-save hook applied.
+    pub(crate) fn to_save(&self) -> Cow<'_, T> {
+        match &self.pre_save_hook {
+            Some(PreSaveHook(hook)) => {
+                let mut value = self.inner.clone();
+                hook(&mut value);
+                Cow::Owned(value)
+            }
+            None => Cow::Borrowed(&self.inner),
+        }
+    }
+}
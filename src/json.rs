@@ -0,0 +1,548 @@
+//! Store settings as JSON.
+
+use std::cell::RefCell;
+
+use serde::{
+    de::DeserializeOwned,
+    ser::{
+        self, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+        SerializeTupleStruct, SerializeTupleVariant,
+    },
+    Serialize, Serializer,
+};
+
+use crate::{Codec, Error, Partial};
+
+/// What [`Json`] does with floats that are infinite or NaN.
+///
+/// RON writes them as `inf`, `-inf` and `NaN`, and [`Toml`](crate::Toml)
+/// as `inf`, `-inf` and `nan`, so only JSON, which has no way to represent
+/// them, needs a policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonFiniteFloat {
+    /// Fail with [`Error::NonFiniteFloat`], naming the offending field.
+    #[default]
+    Error,
+
+    /// Write `null`. Reading the file back fails unless the field is an
+    /// `Option`, which then becomes `None`.
+    Null,
+
+    /// Write `"inf"`, `"-inf"` or `"NaN"`. Reading the file back fails unless
+    /// the field accepts strings.
+    String,
+}
+
+/// A codec storing settings as pretty-printed JSON.
+///
+/// ```rust
+/// # use settings::{Error, Json, NonFiniteFloat, Settings};
+/// # use tempfile::tempdir;
+/// # use std::{error, fs};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+/// struct Limits {
+///     pub max_speed: f64,
+/// }
+///
+/// #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+/// struct Config {
+///     pub limits: Limits,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn error::Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.json");
+/// let input = r#"{ "limits": { "max_speed": 1.5 } }"#;
+///
+/// let mut settings = Settings::<Config>::with_codec(Json::default())
+///     .load_from_reader(input.as_bytes(), path.clone())?;
+/// settings.limits.max_speed = f64::INFINITY;
+/// let result = settings.save();
+/// assert!(matches!(result, Err(Error::NonFiniteFloat { path }) if path == "limits.max_speed"));
+///
+/// let mut settings = Settings::<Config>::with_codec(Json::default().non_finite_float(NonFiniteFloat::Null))
+///     .load_from_reader(input.as_bytes(), path.clone())?;
+/// settings.limits.max_speed = f64::INFINITY;
+/// settings.save()?;
+/// assert_eq!(fs::read_to_string(&path)?, r#"{
+///   "limits": {
+///     "max_speed": null
+///   }
+/// }"#);
+///
+/// let mut settings = Settings::<Config>::with_codec(Json::default().non_finite_float(NonFiniteFloat::String))
+///     .load_from_reader(input.as_bytes(), path.clone())?;
+/// settings.limits.max_speed = f64::NEG_INFINITY;
+/// settings.save()?;
+/// assert_eq!(fs::read_to_string(&path)?, r#"{
+///   "limits": {
+///     "max_speed": "-inf"
+///   }
+/// }"#);
+///
+/// Settings::<Config>::with_codec(Json::default())
+///     .load_from_reader(input.as_bytes(), path.clone())?
+///     .save_minimal()?;
+/// assert_eq!(fs::read_to_string(&path)?, r#"{
+///   "limits": {
+///     "max_speed": 1.5
+///   }
+/// }"#);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Json {
+    non_finite_float: NonFiniteFloat,
+}
+
+impl Json {
+    /// Set what to do with infinite and NaN floats. Defaults to
+    /// [`NonFiniteFloat::Error`].
+    pub fn non_finite_float(mut self, non_finite_float: NonFiniteFloat) -> Self {
+        self.non_finite_float = non_finite_float;
+        self
+    }
+
+    /// Serialize `value`, applying the policy for non-finite floats.
+    fn to_bytes<V>(&self, value: &V) -> Result<Vec<u8>, Error>
+    where
+        V: Serialize + ?Sized,
+    {
+        let found = RefCell::new(None);
+        let checked = Finite {
+            value,
+            path: String::new(),
+            context: Context {
+                policy: self.non_finite_float,
+                found: &found,
+            },
+        };
+        serde_json::to_vec_pretty(&checked).map_err(|err| match found.into_inner() {
+            Some(path) => Error::NonFiniteFloat { path },
+            None => Error::Codec(err.into()),
+        })
+    }
+}
+
+impl<T> Codec<T> for Json
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn deserialize(&self, bytes: &[u8]) -> Result<T, Error> {
+        serde_json::from_slice(bytes).map_err(|err| Error::Codec(err.into()))
+    }
+
+    fn deserialize_value(&self, bytes: &[u8]) -> Result<ron::Value, Error> {
+        serde_json::from_slice(bytes).map_err(|err| Error::Codec(err.into()))
+    }
+
+    fn serialize(&self, value: &T) -> Result<Vec<u8>, Error> {
+        self.to_bytes(value)
+    }
+
+    fn serialize_partial(&self, value: &Partial<'_, T>) -> Result<Vec<u8>, Error> {
+        self.to_bytes(value)
+    }
+
+    fn format(&self) -> &'static str {
+        "json"
+    }
+}
+
+/// Shared state while serializing with [`Finite`].
+#[derive(Clone, Copy)]
+struct Context<'a> {
+    policy: NonFiniteFloat,
+    /// The path of the float that was rejected, if any.
+    found: &'a RefCell<Option<String>>,
+}
+
+/// Serializes `value`, applying the policy to non-finite floats.
+struct Finite<'a, T: ?Sized> {
+    value: &'a T,
+    path: String,
+    context: Context<'a>,
+}
+
+impl<T> Serialize for Finite<'_, T>
+where
+    T: Serialize + ?Sized,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.value.serialize(FiniteSerializer {
+            inner: serializer,
+            path: self.path.clone(),
+            context: self.context,
+        })
+    }
+}
+
+struct FiniteSerializer<'a, S> {
+    inner: S,
+    path: String,
+    context: Context<'a>,
+}
+
+struct FiniteCompound<'a, C> {
+    inner: C,
+    path: String,
+    context: Context<'a>,
+    /// The index of the next element of a sequence or tuple.
+    index: usize,
+    /// The name of the key whose value is serialized next.
+    key: Option<String>,
+}
+
+impl<'a, C> FiniteCompound<'a, C> {
+    fn new(inner: C, path: String, context: Context<'a>) -> Self {
+        Self {
+            inner,
+            path,
+            context,
+            index: 0,
+            key: None,
+        }
+    }
+
+    /// Wrap the next element of a sequence or tuple.
+    fn element<'b, T>(&mut self, value: &'b T) -> Finite<'b, T>
+    where
+        'a: 'b,
+        T: ?Sized,
+    {
+        let path = join(&self.path, &self.index.to_string());
+        self.index += 1;
+        Finite {
+            value,
+            path,
+            context: self.context,
+        }
+    }
+
+    /// Wrap the value of the field or map entry named `key`.
+    fn field<'b, T>(&self, key: &str, value: &'b T) -> Finite<'b, T>
+    where
+        'a: 'b,
+        T: ?Sized,
+    {
+        Finite {
+            value,
+            path: join(&self.path, key),
+            context: self.context,
+        }
+    }
+}
+
+/// The dotted path of `segment` inside `path`.
+fn join(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{}.{}", path, segment)
+    }
+}
+
+/// The name of a map key as it appears in the path.
+fn key_name<K>(key: &K) -> String
+where
+    K: Serialize + ?Sized,
+{
+    match serde_json::to_value(key) {
+        Ok(serde_json::Value::String(key)) => key,
+        Ok(key) => key.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+impl<'a, S> FiniteSerializer<'a, S>
+where
+    S: Serializer,
+{
+    /// Apply the policy to the non-finite float written as `text`.
+    fn non_finite(self, text: String) -> Result<S::Ok, S::Error> {
+        match self.context.policy {
+            NonFiniteFloat::Error => {
+                let message = format!("non-finite float {} at `{}`", text, self.path);
+                *self.context.found.borrow_mut() = Some(self.path);
+                Err(ser::Error::custom(message))
+            }
+            NonFiniteFloat::Null => self.inner.serialize_none(),
+            NonFiniteFloat::String => self.inner.serialize_str(&text),
+        }
+    }
+
+    /// Wrap `value`, found at `path`.
+    fn wrap<'b, T>(&self, value: &'b T, path: String) -> Finite<'b, T>
+    where
+        'a: 'b,
+        T: ?Sized,
+    {
+        Finite {
+            value,
+            path,
+            context: self.context,
+        }
+    }
+}
+
+macro_rules! delegate {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<Self::Ok, Self::Error> {
+                self.inner.$method($($arg),*)
+            }
+        )*
+    };
+}
+
+impl<'a, S> Serializer for FiniteSerializer<'a, S>
+where
+    S: Serializer,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = FiniteCompound<'a, S::SerializeSeq>;
+    type SerializeTuple = FiniteCompound<'a, S::SerializeTuple>;
+    type SerializeTupleStruct = FiniteCompound<'a, S::SerializeTupleStruct>;
+    type SerializeTupleVariant = FiniteCompound<'a, S::SerializeTupleVariant>;
+    type SerializeMap = FiniteCompound<'a, S::SerializeMap>;
+    type SerializeStruct = FiniteCompound<'a, S::SerializeStruct>;
+    type SerializeStructVariant = FiniteCompound<'a, S::SerializeStructVariant>;
+
+    delegate! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str);
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        if v.is_finite() {
+            self.inner.serialize_f32(v)
+        } else {
+            self.non_finite(v.to_string())
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        if v.is_finite() {
+            self.inner.serialize_f64(v)
+        } else {
+            self.non_finite(v.to_string())
+        }
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.wrap(value, self.path.clone());
+        self.inner.serialize_some(&value)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.wrap(value, self.path.clone());
+        self.inner.serialize_newtype_struct(name, &value)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = self.wrap(value, join(&self.path, variant));
+        self.inner
+            .serialize_newtype_variant(name, variant_index, variant, &value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        let inner = self.inner.serialize_seq(len)?;
+        Ok(FiniteCompound::new(inner, self.path, self.context))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        let inner = self.inner.serialize_tuple(len)?;
+        Ok(FiniteCompound::new(inner, self.path, self.context))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        let inner = self.inner.serialize_tuple_struct(name, len)?;
+        Ok(FiniteCompound::new(inner, self.path, self.context))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        let inner = self
+            .inner
+            .serialize_tuple_variant(name, variant_index, variant, len)?;
+        let path = join(&self.path, variant);
+        Ok(FiniteCompound::new(inner, path, self.context))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        let inner = self.inner.serialize_map(len)?;
+        Ok(FiniteCompound::new(inner, self.path, self.context))
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        let inner = self.inner.serialize_struct(name, len)?;
+        Ok(FiniteCompound::new(inner, self.path, self.context))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        let inner = self
+            .inner
+            .serialize_struct_variant(name, variant_index, variant, len)?;
+        let path = join(&self.path, variant);
+        Ok(FiniteCompound::new(inner, path, self.context))
+    }
+}
+
+macro_rules! elements {
+    ($($trait:ident::$method:ident;)*) => {
+        $(
+            impl<C> $trait for FiniteCompound<'_, C>
+            where
+                C: $trait,
+            {
+                type Ok = C::Ok;
+                type Error = C::Error;
+
+                fn $method<T>(&mut self, value: &T) -> Result<(), Self::Error>
+                where
+                    T: Serialize + ?Sized,
+                {
+                    let value = self.element(value);
+                    self.inner.$method(&value)
+                }
+
+                fn end(self) -> Result<Self::Ok, Self::Error> {
+                    self.inner.end()
+                }
+            }
+        )*
+    };
+}
+
+elements! {
+    SerializeSeq::serialize_element;
+    SerializeTuple::serialize_element;
+    SerializeTupleStruct::serialize_field;
+    SerializeTupleVariant::serialize_field;
+}
+
+macro_rules! fields {
+    ($($trait:ident;)*) => {
+        $(
+            impl<C> $trait for FiniteCompound<'_, C>
+            where
+                C: $trait,
+            {
+                type Ok = C::Ok;
+                type Error = C::Error;
+
+                fn serialize_field<T>(
+                    &mut self,
+                    key: &'static str,
+                    value: &T,
+                ) -> Result<(), Self::Error>
+                where
+                    T: Serialize + ?Sized,
+                {
+                    let value = self.field(key, value);
+                    self.inner.serialize_field(key, &value)
+                }
+
+                fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
+                    self.inner.skip_field(key)
+                }
+
+                fn end(self) -> Result<Self::Ok, Self::Error> {
+                    self.inner.end()
+                }
+            }
+        )*
+    };
+}
+
+fields! {
+    SerializeStruct;
+    SerializeStructVariant;
+}
+
+impl<C> SerializeMap for FiniteCompound<'_, C>
+where
+    C: SerializeMap,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key = Some(key_name(key));
+        self.inner.serialize_key(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self.key.take().unwrap_or_default();
+        let value = self.field(&key, value);
+        self.inner.serialize_value(&value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
@@ -1,17 +1,67 @@
 use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
     env,
-    fmt::Debug,
-    fs::File,
-    io::{BufReader, BufWriter},
+    fmt::{self, Debug, Write as _},
+    fs::{self, File},
+    io::{self, BufReader, Read, Write},
+    mem,
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use directories::ProjectDirs;
 use log::debug;
-use ron::ser::PrettyConfig;
 use serde::{de::DeserializeOwned, Serialize};
 
+use crate::trace::Span;
+
+mod accessors;
+mod audit;
+mod background;
+mod builder;
+mod codec;
+mod de;
+mod diff;
+mod hook;
+#[cfg(feature = "json")]
+mod json;
+mod parse;
+#[cfg(any(feature = "figment", feature = "config"))]
+mod provider;
+mod repair;
+#[cfg(feature = "schema")]
+mod schema;
+mod ser;
+pub mod serde_helpers;
+mod shared;
+#[cfg(feature = "toml")]
+mod toml;
+mod trace;
+mod value;
+#[cfg(feature = "watch")]
+mod watch;
+mod writer;
+
+pub use builder::{Builder, ConflictStrategy};
+pub use codec::{Codec, Ron};
+pub use diff::{diff_files, FieldDiff};
+#[cfg(feature = "json")]
+pub use json::{Json, NonFiniteFloat};
+#[cfg(any(feature = "figment", feature = "config"))]
+pub use provider::SettingsProvider;
+#[cfg(feature = "schema")]
+pub use schema::validate_against_schema;
+pub use ser::Partial;
+pub use shared::ArcSettings;
+#[cfg(feature = "toml")]
+pub use toml::Toml;
+#[cfg(feature = "watch")]
+pub use watch::{WatchEvent, Watcher};
+pub use writer::WriterToken;
+
 /// Error type used for all errors in this crate.
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -22,20 +72,119 @@ pub enum Error {
         path: PathBuf,
     },
 
+    /// Emitted when the operating system denied access to a settings file.
+    #[error("Permission denied for settings file")]
+    PermissionDenied { path: PathBuf },
+
+    /// Emitted when the settings file is larger than the
+    /// [configured limit](Builder::max_file_size).
+    #[error("Settings file is {size} bytes, more than the limit of {limit}")]
+    TooLarge { size: u64, limit: usize },
+
+    /// Emitted when the settings path failed validation.
+    #[error("Settings path is not usable")]
+    InvalidPath {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
+    /// Emitted when a path leads outside the
+    /// [base directory](Builder::base_dir).
+    #[error("Settings path escapes the base directory")]
+    PathEscape { path: PathBuf },
+
+    /// Emitted when the lock file for the settings could not be acquired.
+    #[error("Could not lock settings file")]
+    Lock {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
     /// Emitted when an error occured during deserialization.
-    #[error("Could not deserialize settings file")]
-    Deserialize(#[source] ron::de::SpannedError),
+    ///
+    /// `path` is the path of the field that failed, like
+    /// `network.endpoints[2].port`, if it is known.
+    #[error(
+        "Could not deserialize settings file{}",
+        path.as_ref().map(|path| format!(" at `{}`", path)).unwrap_or_default()
+    )]
+    Deserialize {
+        source: ron::de::SpannedError,
+        path: Option<String>,
+    },
 
     /// Emitted when an error occured during serialization.
     #[error("Could not serialize settings file")]
     Serialize(#[source] ron::Error),
 
+    /// Emitted when an intermediate untyped value could not be converted.
+    #[error("Could not convert settings value")]
+    Value(#[source] ron::Error),
+
+    /// Emitted by custom [`Codec`]s.
+    #[error("Could not convert settings file: {0}")]
+    Codec(Box<dyn std::error::Error + Send + Sync>),
+
     /// Emitted when the settings file is not found.
     #[error("Cound not find a settings file")]
     NotFound,
 
     #[error("Could not find project directory")]
     ProjectDirs,
+
+    /// Emitted when the settings were saved but writing to some mirrors failed.
+    #[error("Could not save settings to {} mirror(s)", failures.len())]
+    Mirror { failures: Vec<(PathBuf, Error)> },
+
+    /// Emitted when an override spec could not be parsed or applied.
+    #[error("Invalid override `{0}`")]
+    InvalidOverride(String),
+
+    /// Emitted when a requested key is missing from the settings.
+    #[error("Could not find key `{0}` in settings")]
+    MissingKey(String),
+
+    /// Emitted when saved settings do not read back as the same value; see
+    /// [`Settings::save_verified`].
+    #[error("Saved settings file does not read back as the same value")]
+    RoundTripMismatch { path: PathBuf },
+
+    /// Emitted by a background save that was dropped because a newer one
+    /// replaced it; see [`Settings::save_in_background`].
+    #[error("Background save was superseded by a newer one")]
+    Superseded,
+
+    /// Emitted when saving or taking ownership of a settings file that another
+    /// instance owns; see [`Settings::try_acquire_writer`].
+    #[error("Settings file is owned by another instance")]
+    NotWriter,
+
+    /// Emitted when [`Json`] meets an infinite or NaN float at the dotted
+    /// `path` and is configured to reject it.
+    #[cfg(feature = "json")]
+    #[error("Cannot write non-finite float at `{path}` as JSON")]
+    NonFiniteFloat { path: String },
+
+    /// Emitted when the settings file does not match a JSON Schema.
+    #[cfg(feature = "schema")]
+    #[error("Settings file does not match the schema: {}", errors.join("; "))]
+    SchemaValidation { errors: Vec<String> },
+
+    /// Emitted when the `.env` file could not be loaded.
+    #[cfg(feature = "dotenv")]
+    #[error("Could not load .env file")]
+    Dotenv(#[source] dotenvy::Error),
+
+    /// Emitted when the settings file could not be watched.
+    #[cfg(feature = "watch")]
+    #[error("Could not watch settings file")]
+    Watch(#[source] notify::Error),
+}
+
+impl From<ron::de::SpannedError> for Error {
+    fn from(source: ron::de::SpannedError) -> Self {
+        Error::Deserialize { source, path: None }
+    }
 }
 
 /// A wrapper around a configuration struct.
@@ -75,11 +224,98 @@ pub enum Error {
 /// # Ok(())
 /// # }
 /// ```
+///
+/// The root does not have to be a struct; a sequence or a map works just as
+/// well. Dotted paths, e.g. for [`modify_field_locked`](Self::modify_field_locked)
+/// or [`get_section_typed`](Self::get_section_typed), start with an index or
+/// a key then. Prefer a `BTreeMap` over a `HashMap` at the root, so the
+/// entries are saved in the same order every time.
+///
+/// ```rust
+/// # use settings::Settings;
+/// # use tempfile::tempdir;
+/// # use std::{collections::BTreeMap, error::Error, fs};
+/// # use serde::{Deserialize, Serialize};
+/// use ron::Value;
+///
+/// #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+/// struct Rule {
+///     pub pattern: String,
+///     pub allow: bool,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// let path = dir.path().join("rules.ron");
+/// fs::write(&path, r#"[(pattern: "*.local", allow: true), (pattern: "*", allow: false)]"#)?;
+///
+/// let mut rules = Settings::<Vec<Rule>>::load_from(&path)?;
+/// rules.push(Rule { pattern: "*.lan".to_string(), allow: true });
+/// rules.save()?;
+/// rules.modify_field_locked("1.allow", |allow| *allow = Value::Bool(true))?;
+///
+/// let reloaded = Settings::<Vec<Rule>>::load_from(&path)?;
+/// assert_eq!(*reloaded, *rules);
+/// assert_eq!(reloaded.len(), 3);
+/// assert!(reloaded[1].allow);
+/// assert_eq!(reloaded.get_section_typed::<String>("2.pattern")?, "*.lan");
+///
+/// let path = dir.path().join("hosts.ron");
+/// fs::write(&path, r#"{"web": (pattern: "*.example.com", allow: true)}"#)?;
+///
+/// let mut hosts = Settings::<BTreeMap<String, Rule>>::load_from(&path)?;
+/// hosts.insert("mail".to_string(), Rule { pattern: "mx.*".to_string(), allow: false });
+/// hosts.save()?;
+///
+/// let reloaded = Settings::<BTreeMap<String, Rule>>::load_from(&path)?;
+/// assert_eq!(*reloaded, *hosts);
+/// assert_eq!(reloaded.get_section_typed::<Rule>("mail")?.pattern, "mx.*");
+/// assert_eq!(reloaded.flatten()?["web.allow"], "true");
+/// let content = fs::read_to_string(&path)?;
+/// assert!(content.find("\"mail\"") < content.find("\"web\""));
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug, Clone)]
 pub struct Settings<T> {
     path: PathBuf,
     inner: T,
-    project_dirs: ProjectDirs,
+    project_dirs: Option<ProjectDirs>,
+    mirrors: Vec<PathBuf>,
+    codec: Arc<dyn Codec<T>>,
+    provenance: BTreeMap<String, Layer>,
+    audit_log: Option<PathBuf>,
+    writer: Weak<File>,
+    pre_save_hook: Option<hook::PreSaveHook<T>>,
+    base_dir: Option<PathBuf>,
+    pending: Arc<background::Pending>,
+}
+
+/// Deletes the temporary file made by [`Settings::write_temp`] when dropped.
+#[derive(Debug)]
+#[must_use = "the temporary file is deleted when the guard is dropped"]
+pub struct TempGuard {
+    _path: tempfile::TempPath,
+}
+
+/// Where the value of a top-level field came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Layer {
+    Default,
+    File(PathBuf),
+    Env(String),
+    Override,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layer::Default => write!(f, "default"),
+            Layer::File(path) => write!(f, "file {:?}", path),
+            Layer::Env(var) => write!(f, "env {}", var),
+            Layer::Override => write!(f, "override"),
+        }
+    }
 }
 
 impl<T> Settings<T>
@@ -102,93 +338,2088 @@ where
     /// // macOS:   /Users/Alice/Library/Application Support/com.Foo-Corp.Bar-App
     /// ```
     pub fn load(qualifier: &str, organization: &str, application: &str) -> Result<Self, Error> {
-        const FILE_NAME: &str = "settings.ron";
-        let project_dirs =
-            ProjectDirs::from(qualifier, organization, application).ok_or(Error::ProjectDirs)?;
-
-        let paths = [
-            env::var(format!("{}_CONFIG_PATH", application.to_uppercase()))
-                .ok()
-                .map(PathBuf::from),
-            env::current_dir().ok().map(|dir| dir.join(FILE_NAME)),
-            Some(project_dirs.config_dir().join(FILE_NAME)),
-        ];
+        Self::builder().load(qualifier, organization, application)
+    }
 
-        if let Some(path) = paths.into_iter().flatten().find(|path| path.exists()) {
-            Self::load_from(path, project_dirs)
-        } else {
-            Err(Error::NotFound)
+    /// Describe how [`load`](Self::load) would find the settings file,
+    /// without loading it.
+    ///
+    /// The report names the environment variable and the configuration
+    /// directory, lists each candidate location in order of precedence with
+    /// whether it exists, and ends with the file that would be loaded or why
+    /// loading would fail. It is meant to be attached to support requests.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{env, error::Error, fs};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # env::set_current_dir(&dir)?;
+    /// let path = dir.path().join("custom.ron");
+    /// fs::write(&path, "()")?;
+    /// env::set_var("DIAGAPP_CONFIG_PATH", &path);
+    ///
+    /// let report = Settings::<()>::diagnose("com", "Foo-Corp", "DiagApp");
+    /// println!("{}", report);
+    /// assert!(report.contains("environment variable DIAGAPP_CONFIG_PATH"));
+    /// assert!(report.contains("current directory"));
+    /// assert!(report.contains("configuration directory"));
+    /// assert!(report.contains(&format!("would load {:?}", path)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn diagnose(qualifier: &str, organization: &str, application: &str) -> String {
+        let project_dirs = ProjectDirs::from(qualifier, organization, application);
+        let mut report = String::new();
+        let mut line = |text: fmt::Arguments| {
+            writeln!(report, "{}", text).expect("writing to a string cannot fail");
+        };
+
+        line(format_args!(
+            "environment variable: {}",
+            env_var(application, "CONFIG_PATH")
+        ));
+        match &project_dirs {
+            Some(dirs) => line(format_args!(
+                "configuration directory: {:?}",
+                dirs.config_dir()
+            )),
+            None => line(format_args!(
+                "configuration directory: unknown, no home directory"
+            )),
+        }
+
+        let mut outcome = None;
+        for (index, (source, path)) in candidate_sources(application, project_dirs.as_ref())
+            .into_iter()
+            .enumerate()
+        {
+            let Some(path) = path else {
+                line(format_args!("{}. {}: not set", index + 1, source));
+                continue;
+            };
+            let status = match fs::metadata(&path) {
+                Ok(_) => {
+                    outcome.get_or_insert_with(|| format!("would load {:?}", path));
+                    "exists".to_string()
+                }
+                Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                    outcome.get_or_insert_with(|| {
+                        format!("would fail: permission denied for {:?}", path)
+                    });
+                    "permission denied".to_string()
+                }
+                Err(err) => format!("skipped, {}", err),
+            };
+            line(format_args!(
+                "{}. {}: {:?} {}",
+                index + 1,
+                source,
+                path,
+                status
+            ));
+        }
+
+        let outcome = match (&project_dirs, outcome) {
+            (None, _) => "would fail: could not find project directory".to_string(),
+            (_, Some(outcome)) => outcome,
+            (_, None) => "would fail: no settings file found".to_string(),
+        };
+        line(format_args!("result: {}", outcome));
+        report
+    }
+
+    /// Load the environment variables from the `.env` file at `env_path`,
+    /// then search for the settings like [`load`](Self::load).
+    ///
+    /// Since the `.env` file is loaded first, a `{application}_CONFIG_PATH`
+    /// defined there is respected. The variables stay set afterwards, so
+    /// `{application}_OVERRIDES` from the `.env` file applies to later calls
+    /// of [`load_with_overrides`](Self::load_with_overrides). Variables that
+    /// are already set in the process environment take precedence over the
+    /// `.env` file. A missing `.env` file is an error.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "from .env")"#)?;
+    /// let env_path = dir.path().join(".env");
+    /// fs::write(&env_path, format!("DOTENVAPP_CONFIG_PATH={}\n", path.display()))?;
+    ///
+    /// let settings = Settings::<Config>::load_with_dotenv(&env_path, "com", "Foo-Corp", "DotenvApp")?;
+    /// assert_eq!(settings.foo, "from .env");
+    /// assert_eq!(settings.path(), path);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "dotenv")]
+    pub fn load_with_dotenv<P>(
+        env_path: P,
+        qualifier: &str,
+        organization: &str,
+        application: &str,
+    ) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        debug!("Loading environment from {:?}", env_path.as_ref());
+        dotenvy::from_path(env_path).map_err(Error::Dotenv)?;
+        Self::load(qualifier, organization, application)
+    }
+
+    /// Load the settings file from `explicit` if given, e.g. from a
+    /// `--config` flag, and search like [`load`](Self::load) otherwise.
+    ///
+    /// An explicit path takes precedence over every other location. If it
+    /// does not exist, loading fails instead of falling back to the search.
+    ///
+    /// ```rust
+    /// # use settings::{Error, Settings};
+    /// # use tempfile::tempdir;
+    /// # use std::{env, error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn error::Error>> {
+    /// # let dir = tempdir()?;
+    /// let searched = dir.path().join("settings.ron");
+    /// fs::write(&searched, r#"(foo: "searched")"#)?;
+    /// env::set_var("MYAPP_CONFIG_PATH", &searched);
+    /// let explicit = dir.path().join("explicit.ron");
+    /// fs::write(&explicit, r#"(foo: "explicit")"#)?;
+    ///
+    /// let settings = Settings::<Config>::load_with_explicit(Some(explicit), "com", "Foo-Corp", "MyApp")?;
+    /// assert_eq!(settings.foo, "explicit");
+    ///
+    /// let missing = dir.path().join("missing.ron");
+    /// let result = Settings::<Config>::load_with_explicit(Some(missing), "com", "Foo-Corp", "MyApp");
+    /// assert!(matches!(result, Err(Error::Open { .. })));
+    ///
+    /// let settings = Settings::<Config>::load_with_explicit(None, "com", "Foo-Corp", "MyApp")?;
+    /// assert_eq!(settings.foo, "searched");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_with_explicit(
+        explicit: Option<PathBuf>,
+        qualifier: &str,
+        organization: &str,
+        application: &str,
+    ) -> Result<Self, Error> {
+        match explicit {
+            Some(path) => {
+                let mut settings = Self::load_from(path)?;
+                settings.project_dirs = ProjectDirs::from(qualifier, organization, application);
+                Ok(settings)
+            }
+            None => Self::load(qualifier, organization, application),
         }
     }
 
     /// Load the settings file from the given path.
-    fn load_from<P>(path: P, project_dirs: ProjectDirs) -> Result<Self, Error>
+    ///
+    /// Fails with [`Error::PermissionDenied`] if the file cannot be read
+    /// because of its permissions. [`load`](Self::load) does the same when it
+    /// cannot check whether a candidate exists, instead of moving on to the
+    /// next location.
+    ///
+    /// ```rust
+    /// # use settings::{Error, Settings};
+    /// # use tempfile::tempdir;
+    /// # use std::{env, error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn error::Error>> {
+    /// # #[cfg(unix)]
+    /// # {
+    /// # use std::os::unix::fs::PermissionsExt;
+    /// # let dir = tempdir()?;
+    /// let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "secret")"#)?;
+    /// fs::set_permissions(&path, fs::Permissions::from_mode(0o000))?;
+    /// # // Permissions do not apply to root.
+    /// # if fs::File::open(&path).is_err() {
+    /// let result = Settings::<Config>::load_from(&path);
+    /// assert!(matches!(result, Err(Error::PermissionDenied { .. })));
+    ///
+    /// let hidden = dir.path().join("hidden");
+    /// fs::create_dir(&hidden)?;
+    /// fs::set_permissions(&hidden, fs::Permissions::from_mode(0o000))?;
+    /// env::set_var("PERMAPP_CONFIG_PATH", hidden.join("settings.ron"));
+    /// let result = Settings::<Config>::load("com", "Foo-Corp", "PermApp");
+    /// assert!(matches!(result, Err(Error::PermissionDenied { .. })));
+    /// # fs::set_permissions(&hidden, fs::Permissions::from_mode(0o755))?;
+    /// # }
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_from<P>(path: P) -> Result<Self, Error>
     where
         P: AsRef<Path>,
     {
-        fn inner<T>(path: &Path, project_dirs: ProjectDirs) -> Result<Settings<T>, Error>
-        where
-            T: DeserializeOwned,
-        {
-            debug!("Loading settings from {:?}", path);
+        Self::builder().load_from(path)
+    }
 
-            let file = File::open(path).map_err(|source| Error::Open {
-                source,
-                path: path.to_path_buf(),
-            })?;
-            let reader = BufReader::new(file);
+    /// Load the settings from `reader`, saving them to `path_for_saves`.
+    ///
+    /// See [`Builder::load_from_reader`].
+    pub fn load_from_reader<R>(reader: R, path_for_saves: PathBuf) -> Result<Self, Error>
+    where
+        R: io::Read,
+    {
+        Self::builder().load_from_reader(reader, path_for_saves)
+    }
 
-            let inner: T = ron::de::from_reader(reader).map_err(Error::Deserialize)?;
+    /// Load the settings from standard input, saving them to `path_for_saves`.
+    ///
+    /// See [`Builder::load_from_stdin`].
+    pub fn load_from_stdin(path_for_saves: PathBuf) -> Result<Self, Error> {
+        Self::builder().load_from_stdin(path_for_saves)
+    }
 
-            Ok(Settings {
-                path: path.to_path_buf(),
-                inner,
-                project_dirs,
-            })
+    /// Wrap `inner` without reading a file; it is saved to `path`.
+    pub fn new<P>(inner: T, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_parts(path.as_ref().to_path_buf(), inner)
+    }
+
+    /// Load the settings file from the given path, or wrap the value returned
+    /// by `defaults` if the file does not exist.
+    ///
+    /// `defaults` is only called when the file is missing, so it can compute
+    /// defaults that `T::default()` cannot express, such as paths that differ
+    /// between platforms. The file is not created; call
+    /// [`save`](Self::save) to write it. Any other error, e.g. a file that
+    /// does not parse, is returned as is.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs, path::PathBuf};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub log_dir: PathBuf,
+    /// }
+    ///
+    /// fn defaults() -> Config {
+    ///     let log_dir = if cfg!(windows) { r"C:\ProgramData\app\logs" } else { "/var/log/app" };
+    ///     Config { log_dir: log_dir.into() }
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// let settings = Settings::<Config>::load_or_else(&path, defaults)?;
+    /// assert_eq!(settings.log_dir, defaults().log_dir);
+    /// assert!(!path.exists());
+    ///
+    /// fs::write(&path, r#"(log_dir: "logs")"#)?;
+    /// let settings = Settings::<Config>::load_or_else(&path, defaults)?;
+    /// assert_eq!(settings.log_dir, PathBuf::from("logs"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_or_else<P, F>(path: P, defaults: F) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        F: FnOnce() -> T,
+    {
+        let path = path.as_ref();
+        match Self::load_from(path) {
+            Err(Error::Open { source, .. }) if source.kind() == io::ErrorKind::NotFound => {
+                debug!("No settings file at {:?}, using defaults", path);
+                Ok(Self::new(defaults(), path))
+            }
+            result => result,
         }
-        inner(path.as_ref(), project_dirs)
     }
 
-    /// Save the settings to the last path used.
-    pub fn save(&self) -> Result<(), Error> {
-        self.save_to(&self.path)
+    /// Configure how the settings are loaded.
+    ///
+    /// See [`Builder`] for the available options.
+    pub fn builder() -> Builder<T> {
+        Builder::new()
     }
 
-    /// Save the settings to the given path.
-    pub fn save_to<P>(&self, path: P) -> Result<(), Error>
+    /// Configure how the settings are loaded, storing them using `codec`.
+    ///
+    /// This is a shorthand for `Settings::builder().codec(codec)`.
+    pub fn with_codec<C>(codec: C) -> Builder<T>
+    where
+        C: Codec<T> + 'static,
+    {
+        Self::builder().codec(codec)
+    }
+
+    /// Load the settings file from the given path, filling in missing fields
+    /// from `T::default()`.
+    ///
+    /// Unlike [`load_from`](Self::load_from), a file that lacks some fields
+    /// still loads: the file is parsed into an untyped value and laid over
+    /// the serialized default value, so nested structs are merged field by
+    /// field. Fields present in the file but unknown to `T` are handled
+    /// exactly as in strict loading, i.e. they are ignored unless `T` uses
+    /// `#[serde(deny_unknown_fields)]`.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+    /// enum Theme {
+    ///     Light,
+    ///     Dark,
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub foo: String,
+    ///     pub bar: u32,
+    ///     pub theme: Theme,
+    /// }
+    ///
+    /// impl Default for Config {
+    ///     fn default() -> Self {
+    ///         Self { foo: "default".to_string(), bar: 7, theme: Theme::Light }
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "Hello World", theme: Dark)"#)?;
+    ///
+    /// assert!(Settings::<Config>::load_from(&path).is_err());
+    ///
+    /// let settings = Settings::<Config>::load_from_tolerant(&path)?;
+    /// assert_eq!(settings.foo, "Hello World");
+    /// assert_eq!(settings.bar, 7);
+    /// assert_eq!(settings.theme, Theme::Dark);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_from_tolerant<P>(path: P) -> Result<Self, Error>
     where
         P: AsRef<Path>,
+        T: Default,
     {
-        fn inner<T>(value: &T, path: &Path) -> Result<(), Error>
+        fn inner<T>(path: &Path) -> Result<Settings<T>, Error>
         where
-            T: Serialize,
+            T: Serialize + DeserializeOwned + Default,
         {
-            let file = File::create(&path).map_err(|source| Error::Open {
-                source,
-                path: path.to_path_buf(),
-            })?;
-            let writer = BufWriter::new(file);
-            ron::ser::to_writer_pretty(writer, value, PrettyConfig::default())
-                .map_err(Error::Serialize)
+            debug!("Loading settings tolerantly from {:?}", path);
+
+            let file = parse::from_bytes(&read(path)?)?;
+            let from_file = value::keys(&file);
+
+            let mut merged = value::to_value(&T::default())?;
+            let provenance = value::keys(&merged)
+                .into_iter()
+                .filter(|key| !from_file.contains(key))
+                .map(|key| (key, Layer::Default))
+                .collect();
+            value::merge(&mut merged, file);
+            let inner: T = merged.into_rust().map_err(Error::Value)?;
+
+            let mut settings = Settings::from_parts(path.to_path_buf(), inner);
+            settings.provenance = provenance;
+            Ok(settings)
         }
-        inner(self.deref(), path.as_ref())
+        inner(path.as_ref())
     }
 
-    pub fn project_dirs(&self) -> &ProjectDirs {
-        &self.project_dirs
+    /// Load the settings file like [`load_from_tolerant`](Self::load_from_tolerant)
+    /// and save it again if any fields were filled in from `T::default()`.
+    ///
+    /// After an update adds fields to `T`, this writes them to the file, so
+    /// users can see and edit them. Nested fields count as well, and so do
+    /// fields that are `None` or empty. If the file already contains every
+    /// field, it is not written.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub foo: String,
+    ///     pub added_in_v2: u32,
+    ///     pub optional: Option<u32>,
+    ///     pub list: Vec<u32>,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "Hello World")"#)?;
+    ///
+    /// Settings::<Config>::load_and_upgrade(&path)?;
+    /// assert_eq!(
+    ///     fs::read_to_string(&path)?,
+    ///     "(\n    foo: \"Hello World\",\n    added_in_v2: 0,\n    optional: None,\n    list: [],\n)"
+    /// );
+    ///
+    /// let modified = fs::metadata(&path)?.modified()?;
+    /// Settings::<Config>::load_and_upgrade(&path)?;
+    /// assert_eq!(fs::metadata(&path)?.modified()?, modified);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_and_upgrade<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        T: Default,
+    {
+        let path = path.as_ref();
+        let settings = Self::load_from_tolerant(path)?;
+
+        let file = value::from_file(settings.codec.as_ref(), &read(path)?)?;
+        let added = value::has_new_keys(&file, &value::to_value(settings.deref())?);
+
+        if added {
+            debug!("Adding new fields to settings file {:?}", path);
+            settings.save()?;
+        }
+        Ok(settings)
     }
-}
 
-impl<T> Deref for Settings<T> {
-    type Target = T;
+    /// Open the settings file like [`load`](Self::load) and apply the
+    /// overrides from the environment variable `{application}_OVERRIDES`.
+    ///
+    /// See [`load_from_with_overrides`](Self::load_from_with_overrides) for
+    /// the format of the overrides. If the variable is unset or empty this
+    /// behaves exactly like [`load`](Self::load).
+    pub fn load_with_overrides(
+        qualifier: &str,
+        organization: &str,
+        application: &str,
+    ) -> Result<Self, Error> {
+        let (project_dirs, path) = find(qualifier, organization, application)?;
+        let var = env_var(application, "OVERRIDES");
+        let spec = env::var(&var).unwrap_or_default();
+        let mut settings =
+            Self::load_overridden(&path.ok_or(Error::NotFound)?, &spec, Layer::Env(var))?;
+        settings.project_dirs = Some(project_dirs);
+        Ok(settings)
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.inner
+    /// Load the settings file from the given path and apply the overrides in
+    /// `spec`.
+    ///
+    /// `spec` is a list of `path=value` pairs separated by `;`, where `path`
+    /// is a dotted path to a field, e.g. `network.port=8080;log.level=debug`.
+    /// Each value is parsed as RON, so numbers, booleans and lists keep their
+    /// type; anything that does not parse is used as a plain string. A bare
+    /// identifier like `Dark` selects an enum variant, but also fills a string
+    /// field. Wrap a value in double quotes to include a `;` in it.
+    ///
+    /// An empty `spec` loads the file strictly, exactly like
+    /// [`load_from`](Self::load_from).
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub network: Network,
+    ///     pub greeting: String,
+    ///     pub theme: Theme,
+    ///     pub count: u32,
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Network {
+    ///     pub host: String,
+    ///     pub port: u16,
+    /// }
+    ///
+    /// #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+    /// enum Theme {
+    ///     Light,
+    ///     Dark,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(
+    ///     network: (host: "localhost", port: 80),
+    ///     greeting: "Hello",
+    ///     theme: Light,
+    ///     count: 0,
+    /// )"#)?;
+    ///
+    /// let settings = Settings::<Config>::load_from_with_overrides(
+    ///     &path,
+    ///     r#"network.port=8080;network.host=example.com;greeting="Hello; World""#,
+    /// )?;
+    /// assert_eq!(settings.network.port, 8080);
+    /// assert_eq!(settings.network.host, "example.com");
+    /// assert_eq!(settings.greeting, "Hello; World");
+    /// assert_eq!(settings.theme, Theme::Light);
+    ///
+    /// let settings =
+    ///     Settings::<Config>::load_from_with_overrides(&path, "count=3;theme=Dark;greeting=Hi")?;
+    /// assert_eq!(settings.count, 3);
+    /// assert_eq!(settings.theme, Theme::Dark);
+    /// assert_eq!(settings.greeting, "Hi");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_from_with_overrides<P>(path: P, spec: &str) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::load_overridden(path.as_ref(), spec, Layer::Override)
     }
-}
 
-impl<T> DerefMut for Settings<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
+    /// Load the file at `path` and apply the overrides in `spec`, which
+    /// came from `layer`.
+    fn load_overridden(path: &Path, spec: &str, layer: Layer) -> Result<Self, Error> {
+        if spec.trim().is_empty() {
+            return Self::load_from(path);
+        }
+
+        debug!("Loading settings from {:?} with overrides {:?}", path, spec);
+
+        let mut value = parse::from_bytes(&read(path)?)?;
+
+        let mut provenance = BTreeMap::new();
+        for (key, override_value) in value::parse_overrides(spec)? {
+            value::insert(&mut value, &key, override_value)
+                .ok_or_else(|| Error::InvalidOverride(key.clone()))?;
+            let field = key.split('.').next().unwrap_or_default();
+            provenance.insert(field.to_string(), layer.clone());
+        }
+
+        let inner: T = value.into_rust().map_err(Error::Value)?;
+        let mut settings = Settings::from_parts(path.to_path_buf(), inner);
+        settings.provenance = provenance;
+        Ok(settings)
+    }
+
+    /// Load the settings file at `base_path` with an optional local override.
+    ///
+    /// By convention the base file, e.g. `settings.ron`, is committed to
+    /// version control while a git-ignored `settings.local.ron` next to it
+    /// holds each developer's personal changes. The local file is named after
+    /// the base by inserting `.local` before the extension. If it exists, its
+    /// fields take precedence over those of the base file, nested structs
+    /// being merged field by field, and [`save`](Self::save) writes to the
+    /// local file so the shared one is never touched. Otherwise this behaves
+    /// like [`load_from`](Self::load_from).
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// #     pub bar: u32,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// let base = dir.path().join("settings.ron");
+    /// fs::write(&base, r#"(foo: "shared", bar: 1)"#)?;
+    ///
+    /// let settings = Settings::<Config>::load_with_local_override(&base)?;
+    /// assert_eq!((settings.foo.as_str(), settings.bar), ("shared", 1));
+    ///
+    /// fs::write(dir.path().join("settings.local.ron"), "(bar: 2)")?;
+    /// let settings = Settings::<Config>::load_with_local_override(&base)?;
+    /// assert_eq!((settings.foo.as_str(), settings.bar), ("shared", 2));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_with_local_override<P>(base_path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let base = base_path.as_ref();
+        let local = local_path(base);
+        if local.exists() {
+            debug!("Found local override {:?}", local);
+            Self::builder().load_merged(local.clone(), &[local, base.to_path_buf()])
+        } else {
+            Self::load_from(base)
+        }
+    }
+
+    /// Load the settings from `read_only_base` with the file at `writable`
+    /// laid over it, saving them to `writable` only.
+    ///
+    /// This separates an immutable baseline, e.g. on a read-only volume of a
+    /// container, from the user's changes. Fields in `writable` take
+    /// precedence over those in the base, nested structs being merged field
+    /// by field. `writable` does not have to exist yet; [`save`](Self::save)
+    /// creates it and never touches the base. Since all settings are saved,
+    /// fields once saved keep their value when the base changes later.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// #     pub bar: u32,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// let base = dir.path().join("base.ron");
+    /// fs::write(&base, r#"(foo: "baseline", bar: 1)"#)?;
+    /// let mut permissions = fs::metadata(&base)?.permissions();
+    /// permissions.set_readonly(true);
+    /// fs::set_permissions(&base, permissions)?;
+    /// let writable = dir.path().join("user.ron");
+    ///
+    /// let mut settings = Settings::<Config>::load_overlay(&base, &writable)?;
+    /// assert_eq!((settings.foo.as_str(), settings.bar), ("baseline", 1));
+    /// settings.bar = 2;
+    /// settings.save()?;
+    ///
+    /// let mut settings = Settings::<Config>::load_overlay(&base, &writable)?;
+    /// assert_eq!((settings.foo.as_str(), settings.bar), ("baseline", 2));
+    /// settings.foo = "changed".to_string();
+    /// settings.save()?;
+    ///
+    /// assert_eq!(fs::read_to_string(&base)?, r#"(foo: "baseline", bar: 1)"#);
+    /// assert_eq!(Settings::<Config>::load_from(&writable)?.foo, "changed");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_overlay(read_only_base: &Path, writable: &Path) -> Result<Self, Error> {
+        if writable.exists() {
+            debug!("Laying {:?} over {:?}", writable, read_only_base);
+            let layers = [writable.to_path_buf(), read_only_base.to_path_buf()];
+            Self::builder().load_merged(writable.to_path_buf(), &layers)
+        } else {
+            Ok(Self::load_from(read_only_base)?.with_path(writable))
+        }
+    }
+
+    /// Save the settings to the last path used and to every mirror.
+    ///
+    /// If the primary file cannot be written its error is returned and the
+    /// mirrors are left untouched. Otherwise every mirror is written, and any
+    /// failures are collected into [`Error::Mirror`]; the primary file has
+    /// been saved in that case. Changes to every file are recorded in the
+    /// [audit log](Builder::audit_log), if any. Fails with
+    /// [`Error::NotWriter`] if another instance
+    /// [owns the file](Self::try_acquire_writer).
+    pub fn save(&self) -> Result<(), Error> {
+        self.check_writer(&self.path)?;
+        let value = self.to_save();
+        self.write_to(&self.path, &value)?;
+
+        let failures: Vec<_> = self
+            .mirrors
+            .iter()
+            .filter_map(|mirror| {
+                self.write_to(mirror, &value)
+                    .err()
+                    .map(|err| (mirror.to_path_buf(), err))
+            })
+            .collect();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Mirror { failures })
+        }
+    }
+
+    /// [`save`](Self::save) the settings, then read the file back and check
+    /// that it holds the same value.
+    ///
+    /// Fails with [`Error::RoundTripMismatch`] if the value read back differs
+    /// from the one written, which points to a type that does not survive
+    /// serialization, e.g. because of skipped fields or a custom serde
+    /// implementation. The file has been saved in that case. This costs an
+    /// extra read and deserialization of the file; mirrors are not checked.
+    ///
+    /// ```rust
+    /// # use settings::{Error, Settings};
+    /// # use tempfile::tempdir;
+    /// # use std::error;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub foo: String,
+    ///     #[serde(skip_serializing, default)]
+    ///     pub cached: u32,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// let mut settings = Settings::new(Config { foo: "Hello World".to_string(), cached: 0 }, &path);
+    /// settings.save_verified()?;
+    ///
+    /// settings.cached = 42;
+    /// let result = settings.save_verified();
+    /// assert!(matches!(result, Err(Error::RoundTripMismatch { .. })));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn save_verified(&self) -> Result<(), Error>
+    where
+        T: PartialEq,
+    {
+        self.save()?;
+
+        let read_back = self.codec.deserialize(&read(&self.path)?)?;
+        if read_back == *self.to_save() {
+            Ok(())
+        } else {
+            Err(Error::RoundTripMismatch {
+                path: self.path.clone(),
+            })
+        }
+    }
+
+    /// Copy the settings file to a timestamped backup next to it.
+    ///
+    /// The backup of `settings.ron` is named `settings.ron.{secs}.bak`, where
+    /// `secs` are the seconds since the Unix epoch. A backup made within the
+    /// same second replaces the previous one. Returns the path of the backup.
+    ///
+    /// See [`Builder::recover_on_corrupt`] for restoring from backups.
+    pub fn backup(&self) -> Result<PathBuf, Error> {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let path = self.confined(&self.path)?;
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}.bak", secs));
+        let backup = path.with_file_name(name);
+
+        debug!("Backing up settings to {:?}", backup);
+        write_atomic(&backup, &read(&path)?)?;
+        Ok(backup)
+    }
+
+    /// Write the settings to a new temporary file, e.g. to hand them to a
+    /// subprocess.
+    ///
+    /// The file is created in the system's temporary directory with the same
+    /// extension as the settings file and written with the settings' codec.
+    /// It is deleted when the returned [`TempGuard`] is dropped, so keep the
+    /// guard alive for as long as the file is needed.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// let settings = Settings::new(Config { foo: "snapshot".to_string() }, &path);
+    ///
+    /// let (temp, guard) = settings.write_temp()?;
+    /// assert_eq!(temp.extension(), path.extension());
+    /// assert_eq!(Settings::<Config>::load_from(&temp)?.foo, "snapshot");
+    ///
+    /// drop(guard);
+    /// assert!(!temp.exists());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_temp(&self) -> Result<(PathBuf, TempGuard), Error> {
+        let suffix = self
+            .path
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy()))
+            .unwrap_or_default();
+        let contents = self.codec.serialize(self.deref())?;
+
+        let file = tempfile::Builder::new()
+            .suffix(&suffix)
+            .tempfile()
+            .map_err(|source| open_error(source, &env::temp_dir()))?;
+        let path = file.path().to_path_buf();
+        let open_error = |source| open_error(source, &path);
+        file.as_file().write_all(&contents).map_err(open_error)?;
+        file.as_file().sync_all().map_err(open_error)?;
+
+        debug!("Wrote settings to temporary file {:?}", path);
+        Ok((
+            path.clone(),
+            TempGuard {
+                _path: file.into_temp_path(),
+            },
+        ))
+    }
+
+    /// Add a path that [`save`](Self::save) also writes the settings to.
+    ///
+    /// ```rust
+    /// # use settings::{Error, Settings};
+    /// # use tempfile::tempdir;
+    /// # use std::{error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// # fs::write(&path, r#"(foo: "")"#)?;
+    /// let mut settings = Settings::<Config>::load_from(&path)?;
+    /// settings.add_mirror(dir.path().join("backup.ron"));
+    /// settings.add_mirror(dir.path().join("missing").join("settings.ron"));
+    ///
+    /// settings.foo = "Hello World".to_string();
+    /// match settings.save() {
+    ///     Err(Error::Mirror { failures }) => {
+    ///         assert_eq!(failures.len(), 1);
+    ///         assert_eq!(failures[0].0, dir.path().join("missing").join("settings.ron"));
+    ///     }
+    ///     result => panic!("unexpected result {:?}", result),
+    /// }
+    ///
+    /// assert_eq!(Settings::<Config>::load_from(&path)?.foo, "Hello World");
+    /// assert_eq!(Settings::<Config>::load_from(dir.path().join("backup.ron"))?.foo, "Hello World");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_mirror<P>(&mut self, path: P)
+    where
+        P: AsRef<Path>,
+    {
+        self.mirrors.push(path.as_ref().to_path_buf());
+    }
+
+    /// Save the settings to the given path.
+    ///
+    /// The settings are written to a temporary file next to `path` which then
+    /// replaces `path`, so readers never observe a partially written file.
+    /// If `path` already exists, the codec gets to patch it instead, see
+    /// [`Codec::update`].
+    ///
+    /// Fields are written in declaration order and maps in their iteration
+    /// order; neither the codec nor this method sorts anything. Equal
+    /// settings therefore only produce byte-for-byte identical files if every
+    /// map in them is ordered, like a `BTreeMap`, or stored with
+    /// [`serde_helpers::sorted_map`]. A plain `HashMap`, including one at the
+    /// root, is written in a different order on every run.
+    ///
+    /// The [pre-save hook](Self::set_pre_save_hook), if any, is applied to
+    /// what is written. Fails with [`Error::PathEscape`] if `path` leads
+    /// outside the [base directory](Builder::base_dir) and with
+    /// [`Error::NotWriter`] if another instance
+    /// [owns the file](Self::try_acquire_writer).
+    pub fn save_to<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.write_to(path.as_ref(), &self.to_save())
+    }
+
+    /// Write `value` to `path` with the codec.
+    fn write_to(&self, path: &Path, value: &T) -> Result<(), Error> {
+        self.write_with(path, value, |previous| match previous {
+            Some(previous) => self.codec.update(previous, value),
+            None => self.codec.serialize(value),
+        })
+    }
+
+    /// Write the contents that `contents` produces from the previous ones, if
+    /// any, to `path`, recording the change to `value` in the audit log.
+    fn write_with<F>(&self, path: &Path, value: &T, contents: F) -> Result<(), Error>
+    where
+        F: FnOnce(Option<&[u8]>) -> Result<Vec<u8>, Error>,
+    {
+        self.check_writer(path)?;
+        let path = self.confined(path)?;
+        debug!("Saving settings to {:?}", path);
+        let span = Span::save(&path, self.codec.format());
+
+        let previous = fs::read(&path).ok();
+        let contents = contents(previous.as_deref())?;
+        span.record_bytes(contents.len());
+        write_atomic(&path, &contents)?;
+
+        if let Some(log) = &self.audit_log {
+            audit::record(log, &path, self.codec.as_ref(), previous, value);
+        }
+        Ok(())
+    }
+
+    /// Change a single field in the settings file while holding a lock.
+    ///
+    /// Takes an exclusive lock on `{file}.lock` next to the settings file,
+    /// reloads the file, applies `f` to the field at the dotted `path`, and
+    /// saves the result before releasing the lock. Other instances doing the
+    /// same never lose each other's updates, which makes this suitable for
+    /// counters. The in-memory settings are replaced with the reloaded ones.
+    /// Fails with [`Error::NotWriter`] if another instance
+    /// [owns the file](Self::try_acquire_writer).
+    ///
+    /// The field is handed to `f` as a [`ron::Value`], with unit enum variants
+    /// as strings and other variants as maps with a single entry from the
+    /// variant name to its content. The rest of the file is left as it was.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs, thread};
+    /// # use serde::{Deserialize, Serialize};
+    /// use ron::{Number, Value};
+    ///
+    /// #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+    /// enum Theme {
+    ///     Light,
+    ///     Dark,
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub launch_count: u32,
+    ///     pub theme: Theme,
+    /// }
+    ///
+    /// fn increment(value: &mut Value) {
+    ///     if let Value::Number(Number::Integer(count)) = value {
+    ///         *count += 1;
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, "(launch_count: 0, theme: Light)")?;
+    ///
+    /// let threads: Vec<_> = (0..2)
+    ///     .map(|_| {
+    ///         let path = path.clone();
+    ///         thread::spawn(move || {
+    ///             let mut settings = Settings::<Config>::load_from(&path).unwrap();
+    ///             for _ in 0..50 {
+    ///                 settings.modify_field_locked("launch_count", increment).unwrap();
+    ///             }
+    ///         })
+    ///     })
+    ///     .collect();
+    /// for thread in threads {
+    ///     thread.join().unwrap();
+    /// }
+    ///
+    /// let mut settings = Settings::<Config>::load_from(&path)?;
+    /// assert_eq!(settings.launch_count, 100);
+    /// assert_eq!(settings.theme, Theme::Light);
+    ///
+    /// settings.modify_field_locked("theme", |theme| *theme = Value::String("Dark".into()))?;
+    /// assert_eq!(settings.theme, Theme::Dark);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn modify_field_locked<F>(&mut self, path: &str, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut ron::Value),
+    {
+        self.check_writer(&self.path)?;
+        let file = self.confined(&self.path)?;
+        let _lock = lock(&file)?;
+
+        let mut value = value::from_file(self.codec.as_ref(), &read(&file)?)?;
+        let field = value::get_path_mut(&mut value, path)
+            .ok_or_else(|| Error::MissingKey(path.to_string()))?;
+        let mut untyped = ron::Value::from(mem::replace(field, value::Value::Unit));
+        f(&mut untyped);
+        *field = untyped.into();
+
+        self.inner = value.into_rust().map_err(Error::Value)?;
+        self.save_to(&file)
+    }
+
+    /// Save only the top-level fields that differ from `T::default()` to the
+    /// last path used.
+    ///
+    /// A field is omitted if its serialized form equals that of the default
+    /// value, so `T` needs `#[serde(default)]` to load the file again. A
+    /// sequence at the root is always written whole, since omitting elements
+    /// would shift the following ones. The file is written with
+    /// [`Codec::serialize_partial`], so it keeps the format and the pretty
+    /// printing options of the codec, and custom codecs that do not implement
+    /// it fail with [`Error::Codec`]. Mirrors are not written, but the change
+    /// is recorded in the [audit log](Builder::audit_log). Fails with
+    /// [`Error::NotWriter`] if another instance
+    /// [owns the file](Self::try_acquire_writer).
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// #[serde(default)]
+    /// struct Config {
+    ///     pub name: String,
+    ///     pub port: u16,
+    /// }
+    ///
+    /// impl Default for Config {
+    ///     fn default() -> Self {
+    ///         Self { name: "server".to_string(), port: 8080 }
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// let mut settings = Settings::new(Config::default(), &path);
+    /// settings.port = 9090;
+    /// settings.save_minimal()?;
+    ///
+    /// assert_eq!(fs::read_to_string(&path)?, "(\n    port: 9090,\n)");
+    /// let settings = Settings::<Config>::load_from(&path)?;
+    /// assert_eq!((settings.name.as_str(), settings.port), ("server", 9090));
+    ///
+    /// let settings = Settings::<Config>::builder()
+    ///     .header_comment("Only the changed settings".to_string())
+    ///     .load_from_reader("(port: 9090)".as_bytes(), path.clone())?;
+    /// settings.save_minimal()?;
+    /// assert_eq!(
+    ///     fs::read_to_string(&path)?,
+    ///     "// Only the changed settings\n(\n    port: 9090,\n)"
+    /// );
+    ///
+    /// let pair = Settings::new([0u32, 5], dir.path().join("pair.ron"));
+    /// pair.save_minimal()?;
+    /// assert_eq!(*Settings::<[u32; 2]>::load_from(pair.path())?, [0, 5]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn save_minimal(&self) -> Result<(), Error>
+    where
+        T: Default,
+    {
+        let value = self.to_save();
+        let defaults: BTreeMap<_, _> = ser::fields(&T::default())
+            .map_err(Error::Serialize)?
+            .into_iter()
+            .collect();
+        let keep =
+            |field: &str, value: &str| defaults.get(field).map(String::as_str) != Some(value);
+        let filtered = ser::Partial {
+            value: &*value,
+            keep: &keep,
+            elements: false,
+        };
+        self.write_with(&self.path, &value, |_| {
+            self.codec.serialize_partial(&filtered)
+        })
+    }
+
+    /// Save the settings to the last path used, dropping stale fields.
+    ///
+    /// Only the fields of `T` are ever written, so any key left over in the
+    /// file from an older version of the schema disappears with this save. Use
+    /// [`prune_file`](Self::prune_file) to find out which keys that affects.
+    pub fn save_pruned(&self) -> Result<(), Error> {
+        self.save()
+    }
+
+    /// List the top-level keys in the settings file that are not part of `T`.
+    ///
+    /// These keys would be removed by the next [`save_pruned`](Self::save_pruned).
+    /// The file itself is not modified.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub foo: String,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "Hello World", removed_in_v2: 42)"#)?;
+    ///
+    /// let settings = Settings::<Config>::load_from(&path)?;
+    /// assert_eq!(settings.prune_file()?, vec!["removed_in_v2".to_string()]);
+    ///
+    /// settings.save_pruned()?;
+    /// assert!(settings.prune_file()?.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prune_file(&self) -> Result<Vec<String>, Error> {
+        let file = value::from_file(self.codec.as_ref(), &read(&self.confined(&self.path)?)?)?;
+        let current = value::keys(&value::to_value(self.deref())?);
+
+        let stale = value::keys(&file)
+            .into_iter()
+            .filter(|key| !current.contains(key))
+            .collect();
+        Ok(stale)
+    }
+
+    /// Describe each top-level field with its value and where it came from.
+    ///
+    /// Every line has the form `field: value // layer`, where the value is
+    /// compact RON and, for a sequence at the root, the field is the index of
+    /// an element. The layer is one of
+    ///
+    /// - `default` for fields filled in by [`load_from_tolerant`](Self::load_from_tolerant),
+    /// - `file "path"` for fields read from a settings file,
+    /// - `env VAR` for fields set by [`load_with_overrides`](Self::load_with_overrides),
+    /// - `override` for fields set by [`load_from_with_overrides`](Self::load_from_with_overrides).
+    ///
+    /// The layer reflects how the settings were loaded, not later changes.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{env, error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// #     pub bar: u32,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "Hello World", bar: 1)"#)?;
+    /// env::set_var("MYAPP_CONFIG_PATH", &path);
+    /// env::set_var("MYAPP_OVERRIDES", "bar=2");
+    ///
+    /// let settings = Settings::<Config>::load_with_overrides("com", "Foo-Corp", "MyApp")?;
+    /// let explanation = settings.explain()?;
+    /// let lines: Vec<_> = explanation.lines().collect();
+    /// assert_eq!(lines[0], format!(r#"foo: "Hello World" // file {:?}"#, path));
+    /// assert_eq!(lines[1], "bar: 2 // env MYAPP_OVERRIDES");
+    ///
+    /// let list = dir.path().join("list.ron");
+    /// fs::write(&list, "[1, 2]")?;
+    /// let explanation = Settings::<Vec<u32>>::load_from(&list)?.explain()?;
+    /// assert_eq!(explanation, format!("0: 1 // file {:?}\n1: 2 // file {:?}\n", list, list));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn explain(&self) -> Result<String, Error> {
+        let mut explanation = String::new();
+        for (field, value) in ser::fields(self.deref()).map_err(Error::Serialize)? {
+            let layer = self
+                .provenance
+                .get(&field)
+                .cloned()
+                .unwrap_or_else(|| Layer::File(self.path.clone()));
+            writeln!(explanation, "{}: {} // {}", field, value, layer)
+                .expect("writing to a string cannot fail");
+        }
+        Ok(explanation)
+    }
+
+    /// Write only the listed top-level fields to `path`.
+    ///
+    /// This is useful for sharing the public part of the settings. For a
+    /// sequence at the root, the fields are the indices of the elements, so
+    /// `&["0", "2"]` writes a sequence of the first and the third element. The
+    /// file is written with [`Codec::serialize_partial`], so custom codecs
+    /// that do not implement it fail with [`Error::Codec`]. Neither the saved
+    /// path nor the settings themselves change.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub name: String,
+    ///     pub theme: String,
+    ///     pub token: String,
+    ///     pub password: String,
+    /// }
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Public {
+    ///     pub name: String,
+    ///     pub theme: String,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(name: "me", theme: "dark", token: "t0k3n", password: "hunter2")"#)?;
+    /// let settings = Settings::<Config>::load_from(&path)?;
+    ///
+    /// let public_path = dir.path().join("public.ron");
+    /// settings.export_fields(&public_path, &["name", "theme"])?;
+    ///
+    /// let public = fs::read_to_string(&public_path)?;
+    /// assert!(!public.contains("token") && !public.contains("password"));
+    /// let public: Public = ron::from_str(&public)?;
+    /// assert_eq!((public.name.as_str(), public.theme.as_str()), ("me", "dark"));
+    ///
+    /// let list = Settings::new(vec![10, 20, 30], dir.path().join("list.ron"));
+    /// list.export_fields(&public_path, &["0", "2"])?;
+    /// assert_eq!(fs::read_to_string(&public_path)?, "[\n    10,\n    30,\n]");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn export_fields<P>(&self, path: P, include: &[&str]) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let keep = |field: &str, _: &str| include.contains(&field);
+        let filtered = ser::Partial {
+            value: &*self.to_save(),
+            keep: &keep,
+            elements: true,
+        };
+        let contents = self.codec.serialize_partial(&filtered)?;
+        write_atomic(&self.confined(path.as_ref())?, &contents)
+    }
+
+    /// A hash of the settings, independent of how the file was formatted.
+    ///
+    /// This is the 64-bit FNV-1a hash of the settings serialized as compact
+    /// RON with the entries of every map sorted by key, so equal settings
+    /// always have the same fingerprint, even if they hold a `HashMap`.
+    pub fn fingerprint(&self) -> Result<u64, Error> {
+        let mut canonical = value::to_value(self.deref())?;
+        value::sort_maps(&mut canonical);
+        Ok(fnv1a(canonical.to_string().as_bytes()))
+    }
+
+    /// An entity tag for the settings, suitable for an HTTP `ETag` header.
+    ///
+    /// The tag is the quoted [`fingerprint`](Self::fingerprint) in hex.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{collections::HashMap, error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # enum Theme {
+    /// #     Light,
+    /// #     Dark,
+    /// # }
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// #     pub bar: u32,
+    /// #     pub theme: Theme,
+    /// #     pub labels: HashMap<String, u32>,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// let (compact, pretty) = (dir.path().join("compact.ron"), dir.path().join("pretty.ron"));
+    /// fs::write(&compact, r#"(foo:"Hello World",bar:1,theme:Light,labels:{"a":1,"b":2,"c":3})"#)?;
+    /// fs::write(&pretty, r#"Config(
+    ///     foo: "Hello World",
+    ///     bar: 1,
+    ///     theme: Light,
+    ///     labels: {"c": 3, "a": 1, "b": 2},
+    /// )
+    /// "#)?;
+    ///
+    /// let mut settings = Settings::<Config>::load_from(&compact)?;
+    /// let etag = settings.etag()?;
+    /// assert!(etag.starts_with('"') && etag.ends_with('"'));
+    /// assert_eq!(etag, Settings::<Config>::load_from(&pretty)?.etag()?);
+    ///
+    /// settings.theme = Theme::Dark;
+    /// assert_ne!(etag, settings.etag()?);
+    /// settings.theme = Theme::Light;
+    /// settings.bar = 2;
+    /// assert_ne!(etag, settings.etag()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn etag(&self) -> Result<String, Error> {
+        Ok(format!("\"{:016x}\"", self.fingerprint()?))
+    }
+
+    /// Flatten the settings into dotted keys and string values, e.g. for
+    /// templating.
+    ///
+    /// Fields of nested structs and maps are keyed by their path, like
+    /// `window.width`, and elements of sequences and tuples by their index,
+    /// like `recent.0`. Unit enum variants are written as their name; the
+    /// content of other variants is keyed by the variant name, like
+    /// `mode.Fixed.fps`. Strings are not quoted; other scalars are written as
+    /// compact RON. `None` values and empty collections are left out.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{collections::BTreeMap, error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Window {
+    ///     pub width: u32,
+    ///     pub title: String,
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// enum Mode {
+    ///     Vsync,
+    ///     Fixed { fps: u32 },
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub window: Window,
+    ///     pub recent: Vec<String>,
+    ///     pub scale: f32,
+    ///     pub mode: Mode,
+    ///     pub fallback: Mode,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(
+    ///     window: (width: 800, title: "Main"),
+    ///     recent: ["a.txt", "b.txt"],
+    ///     scale: 1.5,
+    ///     mode: Vsync,
+    ///     fallback: Fixed(fps: 30),
+    /// )"#)?;
+    ///
+    /// let flat = Settings::<Config>::load_from(&path)?.flatten()?;
+    /// let expected: BTreeMap<_, _> = [
+    ///     ("fallback.Fixed.fps", "30"),
+    ///     ("mode", "Vsync"),
+    ///     ("recent.0", "a.txt"),
+    ///     ("recent.1", "b.txt"),
+    ///     ("scale", "1.5"),
+    ///     ("window.title", "Main"),
+    ///     ("window.width", "800"),
+    /// ]
+    /// .into_iter()
+    /// .map(|(key, value)| (key.to_string(), value.to_string()))
+    /// .collect();
+    /// assert_eq!(flat, expected);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn flatten(&self) -> Result<BTreeMap<String, String>, Error> {
+        let mut flat = BTreeMap::new();
+        value::flatten("", &value::to_value(self.deref())?, &mut flat);
+        Ok(flat)
+    }
+
+    /// The settings as environment variable assignments, e.g. to pass them to
+    /// a subprocess or a container.
+    ///
+    /// Every entry of [`flatten`](Self::flatten) becomes one variable: every
+    /// character of its path other than ASCII letters, digits and `_`, like
+    /// the dots, is replaced with `_`, the result is uppercased and appended
+    /// to `prefix` with a `_` in between, unless `prefix` is empty. Array elements are numbered, so `recent: ["a.txt"]` under the
+    /// prefix `APP` becomes `APP_RECENT_0=a.txt`; `None` and empty arrays or
+    /// maps produce no variable. Values are stringified as by
+    /// [`flatten`](Self::flatten), so unit enum variants become their name.
+    /// The variables are sorted by path. Paths that only differ in case or in
+    /// replaced characters map to the same name.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{collections::BTreeMap, error::Error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Database {
+    ///     pub host: String,
+    ///     pub port: u16,
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// enum Theme {
+    ///     Light,
+    ///     Dark,
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub database: Database,
+    ///     pub log_level: Option<String>,
+    ///     pub replicas: Vec<String>,
+    ///     pub debug: bool,
+    ///     pub theme: Theme,
+    ///     pub labels: BTreeMap<String, String>,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(
+    ///     database: (host: "db.local", port: 5432),
+    ///     log_level: None,
+    ///     replicas: ["r1", "r2"],
+    ///     debug: true,
+    ///     theme: Dark,
+    ///     labels: {"team-name": "core"},
+    /// )"#)?;
+    ///
+    /// let vars = Settings::<Config>::load_from(&path)?.to_env_vars("APP")?;
+    /// let vars: Vec<_> = vars.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+    /// assert_eq!(vars, [
+    ///     "APP_DATABASE_HOST=db.local",
+    ///     "APP_DATABASE_PORT=5432",
+    ///     "APP_DEBUG=true",
+    ///     "APP_LABELS_TEAM_NAME=core",
+    ///     "APP_REPLICAS_0=r1",
+    ///     "APP_REPLICAS_1=r2",
+    ///     "APP_THEME=Dark",
+    /// ]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_env_vars(&self, prefix: &str) -> Result<Vec<(String, String)>, Error> {
+        Ok(self
+            .flatten()?
+            .into_iter()
+            .map(|(path, value)| {
+                let name: String = path
+                    .chars()
+                    .map(|c| match c.to_ascii_uppercase() {
+                        c @ ('A'..='Z' | '0'..='9' | '_') => c,
+                        _ => '_',
+                    })
+                    .collect();
+                let name = if prefix.is_empty() {
+                    name
+                } else {
+                    format!("{}_{}", prefix, name)
+                };
+                (name, value)
+            })
+            .collect())
+    }
+
+    /// Deserialize the subtree at the dotted `path` into `S`.
+    ///
+    /// This is the typed counterpart to [`flatten`](Self::flatten) and
+    /// [`load_section`], for pulling out e.g. a nested list of rules. Returns
+    /// [`Error::MissingKey`] if there is nothing at `path` and
+    /// [`Error::Value`] if the subtree does not fit `S`.
+    ///
+    /// ```rust
+    /// # use settings::{Error, Settings};
+    /// # use tempfile::tempdir;
+    /// # use std::{error, fs};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+    /// enum Action {
+    ///     Allow,
+    ///     Deny,
+    ///     Log { level: u8 },
+    /// }
+    ///
+    /// #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+    /// struct Rule {
+    ///     pub pattern: String,
+    ///     pub action: Action,
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Firewall {
+    ///     pub rules: Vec<Rule>,
+    /// }
+    ///
+    /// #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// struct Config {
+    ///     pub firewall: Firewall,
+    /// }
+    ///
+    /// # fn main() -> Result<(), Box<dyn error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(firewall: (rules: [
+    ///     (pattern: "*.local", action: Allow),
+    ///     (pattern: "*", action: Log(level: 2)),
+    /// ]))"#)?;
+    /// let settings = Settings::<Config>::load_from(&path)?;
+    ///
+    /// let rules: Vec<Rule> = settings.get_section_typed("firewall.rules")?;
+    /// assert_eq!(rules[0], Rule { pattern: "*.local".to_string(), action: Action::Allow });
+    /// let action: Action = settings.get_section_typed("firewall.rules.1.action")?;
+    /// assert_eq!(action, Action::Log { level: 2 });
+    ///
+    /// let wrong = settings.get_section_typed::<Vec<u32>>("firewall.rules");
+    /// assert!(matches!(wrong, Err(Error::Value(_))));
+    /// let missing = settings.get_section_typed::<Vec<Rule>>("firewall.zones");
+    /// assert!(matches!(missing, Err(Error::MissingKey(key)) if key == "firewall.zones"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_section_typed<S>(&self, path: &str) -> Result<S, Error>
+    where
+        S: DeserializeOwned,
+    {
+        let text = ron::to_string(self.deref()).map_err(Error::Value)?;
+        ron::Options::default()
+            .from_str_seed(&text, de::Section::path(path))
+            .map_err(|err| Error::Value(err.code))?
+            .ok_or_else(|| Error::MissingKey(path.to_string()))
+    }
+
+    /// The path the settings are saved to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Change the path the settings are saved to.
+    pub fn set_path<P>(&mut self, path: P)
+    where
+        P: AsRef<Path>,
+    {
+        self.path = path.as_ref().to_path_buf();
+    }
+
+    /// Change the path the settings are saved to, for chaining.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::error::Error;
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// let config = Config { foo: "Hello World".to_string() };
+    /// let (default, custom) = (dir.path().join("settings.ron"), dir.path().join("custom.ron"));
+    ///
+    /// Settings::new(config, &default).with_path(&custom).save()?;
+    /// assert!(!default.exists());
+    /// assert_eq!(Settings::<Config>::load_from(&custom)?.foo, "Hello World");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_path<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.set_path(path);
+        self
+    }
+
+    /// When the settings file was last modified, or `None` if it does not
+    /// exist yet.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, time::SystemTime};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// let config = Config { foo: "Hello World".to_string() };
+    /// let settings = Settings::new(config, dir.path().join("settings.ron"));
+    /// assert_eq!(settings.last_modified()?, None);
+    ///
+    /// let before = SystemTime::now() - std::time::Duration::from_secs(1);
+    /// settings.save()?;
+    /// assert!(settings.last_modified()?.unwrap() >= before);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn last_modified(&self) -> Result<Option<SystemTime>, Error> {
+        match fs::metadata(&self.path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => Ok(Some(modified)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(open_error(err, &self.path)),
+        }
+    }
+
+    /// The project directories, if the settings were found using [`load`](Self::load).
+    pub fn project_dirs(&self) -> Option<&ProjectDirs> {
+        self.project_dirs.as_ref()
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Load a single top-level section of the settings file at `path`.
+///
+/// Only the named section is deserialized into `S`; all other sections are
+/// parsed but skipped, so modules can load just the part of the file they
+/// care about. Returns [`Error::MissingKey`] if the section is absent.
+///
+/// ```rust
+/// # use settings::{load_section, Error};
+/// # use tempfile::tempdir;
+/// # use std::{error, fs};
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Network {
+///     pub port: u16,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn error::Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.ron");
+/// fs::write(&path, r#"(
+///     network: (port: 8080),
+///     graphics: (width: 1920, height: 1080, shaders: ["a", "b"]),
+/// )"#)?;
+///
+/// let network: Network = load_section(&path, "network")?;
+/// assert_eq!(network.port, 8080);
+///
+/// let missing = load_section::<Network>(&path, "audio");
+/// assert!(matches!(missing, Err(Error::MissingKey(key)) if key == "audio"));
+/// # Ok(())
+/// # }
+/// ```
+pub fn load_section<S>(path: &Path, section: &str) -> Result<S, Error>
+where
+    S: DeserializeOwned,
+{
+    debug!("Loading section {:?} from {:?}", section, path);
+
+    let reader = open(path)?;
+    ron::Options::default()
+        .from_reader_seed(reader, de::Section::new(section))
+        .map_err(Error::from)?
+        .ok_or_else(|| Error::MissingKey(section.to_string()))
+}
+
+/// Pass each element of the top-level array `field` in the settings file at
+/// `path` to `f`.
+///
+/// Meant for huge lists, like rule sets or allowlists, that should not be held
+/// as a `Vec<I>` at once. The whole file is still read into memory, since RON
+/// cannot be parsed incrementally, but the elements are deserialized one at a
+/// time and handed to `f` right away. Other fields are parsed but skipped.
+/// Returns [`Error::MissingKey`] if the field is absent.
+///
+/// ```rust
+/// # use settings::{load_array_field, Error};
+/// # use tempfile::tempdir;
+/// # use std::{error, fs};
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Rule {
+///     pub port: u32,
+///     pub allow: bool,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn error::Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.ron");
+/// let rules: Vec<_> = (0..100_000)
+///     .map(|port| format!("(port: {}, allow: {})", port, port % 2 == 0))
+///     .collect();
+/// fs::write(&path, format!("(name: \"firewall\", rules: [{}])", rules.join(", ")))?;
+///
+/// let (mut count, mut allowed) = (0, 0);
+/// load_array_field(&path, "rules", |rule: Rule| {
+///     count += 1;
+///     allowed += u32::from(rule.allow);
+/// })?;
+/// assert_eq!((count, allowed), (100_000, 50_000));
+///
+/// let missing = load_array_field(&path, "zones", |_: Rule| {});
+/// assert!(matches!(missing, Err(Error::MissingKey(key)) if key == "zones"));
+/// # Ok(())
+/// # }
+/// ```
+pub fn load_array_field<I, F>(path: &Path, field: &str, f: F) -> Result<(), Error>
+where
+    I: DeserializeOwned,
+    F: FnMut(I),
+{
+    debug!("Loading array field {:?} from {:?}", field, path);
+
+    let reader = open(path)?;
+    ron::Options::default()
+        .from_reader_seed(reader, de::ArrayField::new(field, f))
+        .map_err(Error::from)?
+        .ok_or_else(|| Error::MissingKey(field.to_string()))
+}
+
+/// The name of the settings file looked for by [`Settings::load`].
+const FILE_NAME: &str = "settings.ron";
+
+/// The locations [`Settings::load`] checks for the settings file, in order of
+/// precedence.
+fn candidates(application: &str, project_dirs: &ProjectDirs) -> Vec<PathBuf> {
+    candidate_sources(application, Some(project_dirs))
+        .into_iter()
+        .filter_map(|(_, path)| path)
+        .collect()
+}
+
+/// The locations of [`candidates`], each with a description of where it
+/// comes from, or `None` if it is not available.
+fn candidate_sources(
+    application: &str,
+    project_dirs: Option<&ProjectDirs>,
+) -> [(String, Option<PathBuf>); 3] {
+    let var = env_var(application, "CONFIG_PATH");
+    [
+        (
+            format!("environment variable {}", var),
+            env::var(&var).ok().map(PathBuf::from),
+        ),
+        (
+            "current directory".to_string(),
+            env::current_dir().ok().map(|dir| dir.join(FILE_NAME)),
+        ),
+        (
+            "configuration directory".to_string(),
+            project_dirs.map(|dirs| dirs.config_dir().join(FILE_NAME)),
+        ),
+    ]
+}
+
+/// The name of the environment variable with the given suffix for `application`.
+fn env_var(application: &str, suffix: &str) -> String {
+    format!("{}_{}", application.to_uppercase(), suffix)
+}
+
+/// Find the first existing settings file like [`Settings::load`] does.
+fn find(
+    qualifier: &str,
+    organization: &str,
+    application: &str,
+) -> Result<(ProjectDirs, Option<PathBuf>), Error> {
+    let (project_dirs, paths) = find_all(qualifier, organization, application)?;
+    Ok((project_dirs, paths.into_iter().next()))
+}
+
+/// Find all existing settings files, in order of precedence.
+fn find_all(
+    qualifier: &str,
+    organization: &str,
+    application: &str,
+) -> Result<(ProjectDirs, Vec<PathBuf>), Error> {
+    let project_dirs =
+        ProjectDirs::from(qualifier, organization, application).ok_or(Error::ProjectDirs)?;
+    let mut paths = Vec::new();
+    for path in candidates(application, &project_dirs) {
+        match fs::metadata(&path) {
+            Ok(_) => paths.push(path),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                return Err(Error::PermissionDenied { path })
+            }
+            Err(_) => {}
+        }
+    }
+    Ok((project_dirs, paths))
+}
+
+/// The local override belonging to `path`, e.g. `settings.local.ron` for
+/// `settings.ron`.
+fn local_path(path: &Path) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(".local");
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// The directory containing `path`.
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// The backups of the file at `path` made by [`Settings::backup`], newest first.
+fn backups(path: &Path) -> Vec<PathBuf> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let Ok(entries) = fs::read_dir(parent_dir(path)) else {
+        return Vec::new();
+    };
+
+    let mut backups: Vec<_> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let secs: u64 = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix(name.as_ref())?
+                .strip_prefix('.')?
+                .strip_suffix(".bak")?
+                .parse()
+                .ok()?;
+            Some((secs, path.with_file_name(entry.file_name())))
+        })
+        .collect();
+    backups.sort_by_key(|(secs, _)| Reverse(*secs));
+    backups.into_iter().map(|(_, backup)| backup).collect()
+}
+
+/// Write `contents` to `path` by writing a temporary file next to it and
+/// renaming that into place.
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let open_error = |source| open_error(source, path);
+
+    let mut file = tempfile::NamedTempFile::new_in(parent_dir(path)).map_err(open_error)?;
+    file.write_all(contents).map_err(open_error)?;
+    file.as_file().sync_all().map_err(open_error)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(file.path(), metadata.permissions()).map_err(open_error)?;
+    }
+    file.persist(path).map_err(|err| open_error(err.error))?;
+    Ok(())
+}
+
+/// Take an exclusive lock on the lock file belonging to `path`.
+///
+/// The lock is released when the returned file is dropped.
+fn lock(path: &Path) -> Result<File, Error> {
+    let path = lock_path(path, "lock");
+
+    let lock_error = |source| Error::Lock {
+        source,
+        path: path.clone(),
+    };
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(lock_error)?;
+    file.lock().map_err(lock_error)?;
+    Ok(file)
+}
+
+/// The path of the lock file with `extension` belonging to `path`.
+fn lock_path(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
+
+/// Resolve `path` against `base` and check that it stays inside.
+///
+/// Relative paths are taken relative to `base`. The result is canonicalized,
+/// so `..` and symlinks are resolved before the check. Parts of the path that
+/// do not exist yet are appended to their nearest existing ancestor; a `..`
+/// among them is rejected.
+fn confine(base: &Path, path: &Path) -> Result<PathBuf, Error> {
+    let invalid = |source, path: &Path| Error::InvalidPath {
+        source,
+        path: path.to_path_buf(),
+    };
+    let escape = || Error::PathEscape {
+        path: path.to_path_buf(),
+    };
+
+    let base = base
+        .canonicalize()
+        .map_err(|source| invalid(source, base))?;
+    let joined = base.join(path);
+    let mut existing = joined.as_path();
+    let mut missing = Vec::new();
+    let mut resolved = loop {
+        match existing.canonicalize() {
+            Ok(resolved) => break resolved,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                missing.push(existing.file_name().ok_or_else(escape)?);
+                existing = existing.parent().ok_or_else(escape)?;
+            }
+            Err(source) => return Err(invalid(source, path)),
+        }
+    };
+    resolved.extend(missing.into_iter().rev());
+
+    if resolved.starts_with(&base) {
+        Ok(resolved)
+    } else {
+        Err(escape())
+    }
+}
+
+/// Read the whole file at `path`.
+fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    fs::read(path).map_err(|source| open_error(source, path))
+}
+
+/// Read the whole file at `path`, failing with [`Error::TooLarge`] instead if
+/// it is longer than `limit` bytes.
+fn read_limited(path: &Path, limit: usize) -> Result<Vec<u8>, Error> {
+    let file = File::open(path).map_err(|source| open_error(source, path))?;
+    let size = file
+        .metadata()
+        .map_err(|source| open_error(source, path))?
+        .len();
+    if size > limit as u64 {
+        return Err(Error::TooLarge { size, limit });
+    }
+
+    // The file may have grown since it was checked.
+    let mut bytes = Vec::with_capacity(size as usize);
+    file.take(limit as u64 + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|source| open_error(source, path))?;
+    if bytes.len() > limit {
+        return Err(Error::TooLarge {
+            size: bytes.len() as u64,
+            limit,
+        });
+    }
+    Ok(bytes)
+}
+
+/// Open the file at `path` for buffered reading.
+fn open(path: &Path) -> Result<BufReader<File>, Error> {
+    let file = File::open(path).map_err(|source| open_error(source, path))?;
+    Ok(BufReader::new(file))
+}
+
+/// The error for failing to access the file at `path`.
+fn open_error(source: io::Error, path: &Path) -> Error {
+    match source.kind() {
+        io::ErrorKind::PermissionDenied => Error::PermissionDenied {
+            path: path.to_path_buf(),
+        },
+        _ => Error::Open {
+            source,
+            path: path.to_path_buf(),
+        },
+    }
+}
+
+impl<T> Settings<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn from_parts(path: PathBuf, inner: T) -> Self {
+        Self {
+            path,
+            inner,
+            project_dirs: None,
+            mirrors: Vec::new(),
+            codec: Arc::new(Ron::default()),
+            provenance: BTreeMap::new(),
+            audit_log: None,
+            writer: Weak::new(),
+            pre_save_hook: None,
+            base_dir: None,
+            pending: Arc::default(),
+        }
+    }
+}
+
+impl<T> Settings<T> {
+    /// Check that `path` stays inside the [base directory](Builder::base_dir),
+    /// if any, returning the path to use.
+    pub(crate) fn confined(&self, path: &Path) -> Result<PathBuf, Error> {
+        match &self.base_dir {
+            Some(base) => confine(base, path),
+            None => Ok(path.to_path_buf()),
+        }
+    }
+}
+
+impl<T> Deref for Settings<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for Settings<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+/// Pass settings to code written against `AsRef`:
+///
+/// ```rust
+/// # use settings::Settings;
+/// # use tempfile::tempdir;
+/// # use std::{error::Error, fs};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Debug, Clone, Deserialize, Serialize)]
+/// struct Config {
+///     pub foo: String,
+/// }
+///
+/// fn greeting(config: impl AsRef<Config>) -> String {
+///     format!("{}!", config.as_ref().foo)
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.ron");
+/// fs::write(&path, r#"(foo: "Hello World")"#)?;
+/// let settings = Settings::<Config>::load_from(&path)?;
+/// assert_eq!(greeting(&settings), "Hello World!");
+/// # Ok(())
+/// # }
+/// ```
+impl<T> AsRef<T> for Settings<T> {
+    fn as_ref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> AsMut<T> for Settings<T> {
+    fn as_mut(&mut self) -> &mut T {
         &mut self.inner
     }
 }
@@ -0,0 +1,364 @@
+//! Parse RON into untyped [`Value`]s, keeping the names [`ron::Value`] drops.
+
+use ron::{
+    de::{Position, SpannedError},
+    Error as Code,
+};
+use serde::de::{DeserializeOwned, IgnoredAny};
+
+use crate::{
+    value::{Number, Value},
+    Error,
+};
+
+/// Parse a whole RON document.
+///
+/// The document is checked by ron first, so invalid files fail with the same
+/// errors as when they are deserialized into `T`.
+pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Value, Error> {
+    ron::de::from_bytes::<IgnoredAny>(bytes)?;
+    Ok(from_str(&String::from_utf8_lossy(bytes))?)
+}
+
+/// Parse a whole RON document, failing if anything but whitespace, comments
+/// and attributes follows the value.
+pub(crate) fn from_str(text: &str) -> Result<Value, SpannedError> {
+    let mut parser = Parser { text, pos: 0 };
+    parser.attributes()?;
+    let value = parser.value()?;
+    parser.skip_ws()?;
+    if parser.pos < text.len() {
+        return Err(parser.error(Code::TrailingCharacters));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn error(&self, code: Code) -> SpannedError {
+        let before = &self.text[..self.pos];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        SpannedError {
+            code,
+            position: Position {
+                line: before.matches('\n').count() + 1,
+                col: before[line_start..].chars().count() + 1,
+            },
+        }
+    }
+
+    /// Consume `token` if the input continues with it.
+    fn consume(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: char, code: Code) -> Result<(), SpannedError> {
+        self.skip_ws()?;
+        if self.peek() == Some(token) {
+            self.pos += token.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error(code))
+        }
+    }
+
+    /// Skip whitespace, line comments and nested block comments.
+    fn skip_ws(&mut self) -> Result<(), SpannedError> {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+
+            if self.consume("//") {
+                let rest = self.rest();
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if self.rest().starts_with("/*") {
+                let mut depth = 0;
+                loop {
+                    if self.consume("/*") {
+                        depth += 1;
+                    } else if self.consume("*/") {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else if let Some(c) = self.peek() {
+                        self.pos += c.len_utf8();
+                    } else {
+                        return Err(self.error(Code::UnclosedBlockComment));
+                    }
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Skip inner attributes like `#![enable(implicit_some)]`.
+    fn attributes(&mut self) -> Result<(), SpannedError> {
+        self.skip_ws()?;
+        while self.consume("#![") {
+            let end = self
+                .rest()
+                .find(']')
+                .ok_or_else(|| self.error(Code::ExpectedAttributeEnd))?;
+            self.pos += end + 1;
+            self.skip_ws()?;
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, SpannedError> {
+        self.skip_ws()?;
+        let rest = self.rest();
+        match self.peek() {
+            None => Err(self.error(Code::Eof)),
+            Some('[') => {
+                self.pos += 1;
+                Ok(Value::Seq(self.elements(']')?))
+            }
+            Some('{') => {
+                self.pos += 1;
+                self.map()
+            }
+            Some('(') => {
+                self.pos += 1;
+                self.parens()
+            }
+            Some('"') => self.string(),
+            Some('\'') => self.char(),
+            Some('r')
+                if rest.starts_with("r\"") || rest.starts_with("r#") && !is_raw_ident(rest) =>
+            {
+                self.raw_string()
+            }
+            Some('0'..='9' | '+' | '-' | '.') => self.number(),
+            Some(_) => {
+                let start = self.pos;
+                let ident = self.ident()?;
+                match ident.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "None" => Ok(Value::Option(None)),
+                    "inf" | "NaN" => {
+                        self.pos = start;
+                        self.number()
+                    }
+                    "Some" => {
+                        self.expect('(', Code::ExpectedOption)?;
+                        let value = self.value()?;
+                        self.skip_ws()?;
+                        self.consume(",");
+                        self.expect(')', Code::ExpectedOptionEnd)?;
+                        Ok(Value::Option(Some(Box::new(value))))
+                    }
+                    _ => {
+                        self.skip_ws()?;
+                        let content = if self.consume("(") {
+                            self.parens()?
+                        } else {
+                            Value::Unit
+                        };
+                        Ok(Value::Named(ident, Box::new(content)))
+                    }
+                }
+            }
+        }
+    }
+
+    /// An identifier, possibly raw like `r#type`.
+    fn ident(&mut self) -> Result<String, SpannedError> {
+        let raw = is_raw_ident(self.rest());
+        if raw {
+            self.pos += 2;
+        }
+        let rest = self.rest();
+        let len = rest
+            .char_indices()
+            .find(|&(index, c)| {
+                let valid = c == '_'
+                    || c.is_ascii_alphabetic()
+                    || index > 0 && c.is_ascii_digit()
+                    || raw && (c.is_ascii_digit() || matches!(c, '.' | '+' | '-'));
+                !valid
+            })
+            .map_or(rest.len(), |(index, _)| index);
+        if len == 0 {
+            return Err(self.error(match self.peek() {
+                Some(c) => Code::UnexpectedByte(c),
+                None => Code::Eof,
+            }));
+        }
+        self.pos += len;
+        Ok(rest[..len].to_string())
+    }
+
+    /// The values up to `end`, separated by commas.
+    fn elements(&mut self, end: char) -> Result<Vec<Value>, SpannedError> {
+        let mut elements = Vec::new();
+        loop {
+            self.skip_ws()?;
+            if self.consume(end.encode_utf8(&mut [0; 4])) {
+                return Ok(elements);
+            }
+            elements.push(self.value()?);
+            self.skip_ws()?;
+            if !self.consume(",") {
+                self.expect(end, Code::ExpectedComma)?;
+                return Ok(elements);
+            }
+        }
+    }
+
+    /// The entries of a map, after its opening brace.
+    fn map(&mut self) -> Result<Value, SpannedError> {
+        let mut entries = Vec::new();
+        loop {
+            self.skip_ws()?;
+            if self.consume("}") {
+                return Ok(Value::Map(entries));
+            }
+            let key = self.value()?;
+            self.expect(':', Code::ExpectedMapColon)?;
+            entries.push((key, self.value()?));
+            self.skip_ws()?;
+            if !self.consume(",") {
+                self.expect('}', Code::ExpectedMapEnd)?;
+                return Ok(Value::Map(entries));
+            }
+        }
+    }
+
+    /// The content of parentheses, after the opening one: the fields of a
+    /// struct, the elements of a tuple, or nothing.
+    fn parens(&mut self) -> Result<Value, SpannedError> {
+        self.skip_ws()?;
+        if self.consume(")") {
+            return Ok(Value::Unit);
+        }
+
+        let start = self.pos;
+        let is_struct = self.ident().is_ok() && {
+            self.skip_ws()?;
+            self.rest().starts_with(':')
+        };
+        self.pos = start;
+        if !is_struct {
+            return Ok(Value::Tuple(self.elements(')')?));
+        }
+
+        let mut fields = Vec::new();
+        loop {
+            self.skip_ws()?;
+            if self.consume(")") {
+                return Ok(Value::Map(fields));
+            }
+            let name = self.ident()?;
+            self.expect(':', Code::ExpectedMapColon)?;
+            fields.push((Value::String(name), self.value()?));
+            self.skip_ws()?;
+            if !self.consume(",") {
+                self.expect(')', Code::ExpectedStructLikeEnd)?;
+                return Ok(Value::Map(fields));
+            }
+        }
+    }
+
+    /// Parse the next `len` bytes as `V` with ron.
+    fn token<V>(&mut self, len: usize) -> Result<V, SpannedError>
+    where
+        V: DeserializeOwned,
+    {
+        let token = &self.text[self.pos..self.pos + len];
+        let value = ron::from_str(token).map_err(|err| self.error(err.code))?;
+        self.pos += len;
+        Ok(value)
+    }
+
+    /// The length of a quoted token starting at the current position,
+    /// including both quotes.
+    fn quoted_len(&self, quote: char) -> Result<usize, SpannedError> {
+        let mut escaped = false;
+        for (index, c) in self.rest().char_indices().skip(1) {
+            match c {
+                '\\' if !escaped => escaped = true,
+                c if c == quote && !escaped => return Ok(index + 1),
+                _ => escaped = false,
+            }
+        }
+        Err(self.error(Code::ExpectedStringEnd))
+    }
+
+    fn string(&mut self) -> Result<Value, SpannedError> {
+        let len = self.quoted_len('"')?;
+        self.token(len).map(Value::String)
+    }
+
+    fn char(&mut self) -> Result<Value, SpannedError> {
+        let len = self.quoted_len('\'')?;
+        self.token(len).map(Value::Char)
+    }
+
+    fn raw_string(&mut self) -> Result<Value, SpannedError> {
+        let rest = self.rest();
+        let hashes = rest[1..].len() - rest[1..].trim_start_matches('#').len();
+        let closing = format!("\"{}", "#".repeat(hashes));
+        let body = 1 + hashes + 1;
+        let end = rest
+            .get(body..)
+            .and_then(|tail| tail.find(&closing))
+            .ok_or_else(|| self.error(Code::ExpectedStringEnd))?;
+        self.token(body + end + closing.len()).map(Value::String)
+    }
+
+    fn number(&mut self) -> Result<Value, SpannedError> {
+        let rest = self.rest();
+        let unsigned = rest.trim_start_matches(['+', '-']);
+        let sign = rest.len() - unsigned.len();
+        let hex = unsigned.starts_with("0x") || unsigned.starts_with("0X");
+        let mut previous = ' ';
+        let len = unsigned
+            .char_indices()
+            .find(|&(_, c)| {
+                let valid = c.is_ascii_alphanumeric()
+                    || matches!(c, '_' | '.')
+                    || matches!(c, '+' | '-') && !hex && matches!(previous, 'e' | 'E');
+                previous = c;
+                !valid
+            })
+            .map_or(unsigned.len(), |(index, _)| index);
+
+        let len = sign + len;
+        let number = if let Ok(integer) = self.token::<i64>(len) {
+            Number::I64(integer)
+        } else if let Ok(integer) = self.token::<u64>(len) {
+            Number::U64(integer)
+        } else {
+            Number::F64(self.token::<f64>(len)?)
+        };
+        Ok(Value::Number(number))
+    }
+}
+
+/// Whether `rest` starts with a raw identifier like `r#type`.
+fn is_raw_ident(rest: &str) -> bool {
+    rest.strip_prefix("r#")
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(|c| c == '_' || c.is_ascii_alphanumeric())
+}
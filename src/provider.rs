@@ -0,0 +1,180 @@
+//! Use the settings file as a source for `figment` or `config`.
+
+use std::path::{Path, PathBuf};
+
+use crate::{find, Error};
+
+/// Where a [`SettingsProvider`] looks for the settings file.
+#[derive(Debug, Clone)]
+enum Location {
+    Search {
+        qualifier: String,
+        organization: String,
+        application: String,
+    },
+    Path(PathBuf),
+}
+
+/// A source that reads the settings file for another configuration library.
+///
+/// With the `figment` feature this implements [`figment::Provider`], with the
+/// `config` feature it implements [`config::Source`]. Either way the file is
+/// located exactly like [`Settings::load`](crate::Settings::load) locates it.
+#[derive(Debug, Clone)]
+pub struct SettingsProvider {
+    location: Location,
+    required: bool,
+}
+
+impl SettingsProvider {
+    /// Search for the settings file given a qualifier, organization, and
+    /// application name.
+    pub fn new(qualifier: &str, organization: &str, application: &str) -> Self {
+        Self {
+            location: Location::Search {
+                qualifier: qualifier.to_string(),
+                organization: organization.to_string(),
+                application: application.to_string(),
+            },
+            required: true,
+        }
+    }
+
+    /// Read the settings file at the given path.
+    pub fn from_path<P>(path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            location: Location::Path(path.as_ref().to_path_buf()),
+            required: true,
+        }
+    }
+
+    /// Set whether a missing settings file is an error.
+    ///
+    /// Defaults to `true`, matching [`Error::NotFound`] from
+    /// [`Settings::load`](crate::Settings::load). When set to `false`, a
+    /// missing file provides no data instead.
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// The settings file to read, or `None` if no candidate exists.
+    fn resolve(&self) -> Result<Option<PathBuf>, Error> {
+        match &self.location {
+            Location::Search {
+                qualifier,
+                organization,
+                application,
+            } => Ok(find(qualifier, organization, application)?.1),
+            Location::Path(path) => Ok(Some(path.clone()).filter(|path| path.exists())),
+        }
+    }
+}
+
+/// Layer the settings file below environment variables:
+///
+/// ```rust
+/// # use settings::SettingsProvider;
+/// # use tempfile::tempdir;
+/// # use std::{error::Error, fs};
+/// use figment::{providers::Env, Figment};
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Config {
+///     pub foo: String,
+///     pub bar: u32,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.ron");
+/// fs::write(&path, r#"(foo: "from file", bar: 1)"#)?;
+/// std::env::set_var("MYAPP_CONFIG_PATH", &path);
+/// std::env::set_var("MYAPP_BAR", "2");
+///
+/// let config: Config = Figment::new()
+///     .merge(SettingsProvider::new("com", "Foo-Corp", "MyApp"))
+///     .merge(Env::prefixed("MYAPP_").ignore(&["CONFIG_PATH"]))
+///     .extract()?;
+/// assert_eq!(config.foo, "from file");
+/// assert_eq!(config.bar, 2);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "figment")]
+impl figment::Provider for SettingsProvider {
+    fn metadata(&self) -> figment::Metadata {
+        let metadata = figment::Metadata::named("RON settings file");
+        match self.resolve() {
+            Ok(Some(path)) => metadata.source(figment::Source::File(path)),
+            _ => metadata,
+        }
+    }
+
+    fn data(
+        &self,
+    ) -> Result<figment::value::Map<figment::Profile, figment::value::Dict>, figment::Error> {
+        let path = match self.resolve() {
+            Ok(Some(path)) => path,
+            Ok(None) if !self.required => return Ok(Default::default()),
+            Ok(None) => return Err(Error::NotFound.to_string().into()),
+            Err(err) => return Err(err.to_string().into()),
+        };
+
+        let reader = crate::open(&path).map_err(|err| err.to_string())?;
+        let value: ron::Value = ron::de::from_reader(reader).map_err(|err| err.to_string())?;
+        let dict = figment::value::Value::serialize(value)?
+            .into_dict()
+            .ok_or_else(|| format!("{:?} does not contain a struct or map", path))?;
+        Ok(figment::Profile::Default.collect(dict))
+    }
+}
+
+/// Layer the settings file below environment variables:
+///
+/// ```rust
+/// # use settings::SettingsProvider;
+/// # use tempfile::tempdir;
+/// # use std::{error::Error, fs};
+/// use config::{Config, Environment};
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.ron");
+/// fs::write(&path, r#"(foo: "from file", bar: 1)"#)?;
+/// std::env::set_var("MYAPP_BAR", "2");
+///
+/// let config = Config::builder()
+///     .add_source(SettingsProvider::from_path(&path))
+///     .add_source(Environment::with_prefix("MYAPP"))
+///     .build()?;
+/// assert_eq!(config.get_string("foo")?, "from file");
+/// assert_eq!(config.get_int("bar")?, 2);
+///
+/// let missing = SettingsProvider::from_path(dir.path().join("missing.ron"));
+/// assert!(Config::builder().add_source(missing.clone()).build().is_err());
+/// assert!(Config::builder().add_source(missing.required(false)).build().is_ok());
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "config")]
+impl config::Source for SettingsProvider {
+    fn clone_into_box(&self) -> Box<dyn config::Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<config::Map<String, config::Value>, config::ConfigError> {
+        match self.resolve() {
+            Ok(Some(path)) => config::File::from(path)
+                .format(config::FileFormat::Ron)
+                .collect(),
+            Ok(None) if !self.required => Ok(Default::default()),
+            Ok(None) => Err(config::ConfigError::Foreign(Box::new(Error::NotFound))),
+            Err(err) => Err(config::ConfigError::Foreign(Box::new(err))),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Repairs for common mistakes in hand-edited RON.
+
+/// Remove commas that RON does not accept.
+///
+/// Collapses repeated commas, drops commas directly after an opening bracket
+/// and removes a comma after the root value. String and character literals as
+/// well as comments are left untouched. Returns `None` if nothing had to be
+/// repaired.
+pub(crate) fn commas(input: &str) -> Option<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut depth = 0usize;
+    let mut last: Option<char> = None;
+    let mut trailing_comma = None;
+    let mut repaired = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                output.push(c);
+                copy_until_quote(&mut chars, &mut output, '"');
+            }
+            '\'' => {
+                output.push(c);
+                copy_until_quote(&mut chars, &mut output, '\'');
+            }
+            'r' if matches!(chars.peek(), Some('#' | '"')) => {
+                output.push(c);
+                copy_raw_string(&mut chars, &mut output);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                output.push(c);
+                for c in chars.by_ref() {
+                    output.push(c);
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                output.push(c);
+                output.push(chars.next().unwrap_or_default());
+                let mut previous = None;
+                for c in chars.by_ref() {
+                    output.push(c);
+                    if previous == Some('*') && c == '/' {
+                        break;
+                    }
+                    previous = Some(c);
+                }
+                continue;
+            }
+            ',' if matches!(last, None | Some(',' | '(' | '[' | '{')) => {
+                repaired = true;
+                continue;
+            }
+            ',' if depth == 0 => {
+                trailing_comma = Some(output.len());
+                output.push(c);
+            }
+            '(' | '[' | '{' => {
+                depth += 1;
+                output.push(c);
+            }
+            ')' | ']' | '}' => {
+                depth = depth.saturating_sub(1);
+                output.push(c);
+            }
+            c => output.push(c),
+        }
+
+        if !c.is_whitespace() {
+            last = Some(c);
+        }
+    }
+
+    if last == Some(',') {
+        if let Some(index) = trailing_comma {
+            output.remove(index);
+            repaired = true;
+        }
+    }
+
+    repaired.then_some(output)
+}
+
+/// Copy a string or character literal up to and including the closing quote.
+fn copy_until_quote<I>(chars: &mut I, output: &mut String, quote: char)
+where
+    I: Iterator<Item = char>,
+{
+    let mut escaped = false;
+    for c in chars {
+        output.push(c);
+        if c == quote && !escaped {
+            break;
+        }
+        escaped = c == '\\' && !escaped;
+    }
+}
+
+/// Copy a raw string literal like `r#"..."#`, starting after the `r`.
+fn copy_raw_string<I>(chars: &mut std::iter::Peekable<I>, output: &mut String)
+where
+    I: Iterator<Item = char>,
+{
+    let mut hashes = 0;
+    while let Some(&c) = chars.peek() {
+        chars.next();
+        output.push(c);
+        if c == '"' {
+            break;
+        }
+        hashes += 1;
+    }
+
+    let mut closing = None;
+    for c in chars.by_ref() {
+        output.push(c);
+        closing = match (c, closing) {
+            ('"', _) => Some(0),
+            ('#', Some(count)) => Some(count + 1),
+            _ => None,
+        };
+        if closing == Some(hashes) {
+            break;
+        }
+    }
+}
+
+/// Remove the struct name `name` that ends at `position`.
+///
+/// `position` is where RON reported a struct name differing from the
+/// expected one, i.e. directly after the name. Returns `None` if `name` does
+/// not end there.
+pub(crate) fn struct_name(input: &str, position: ron::de::Position, name: &str) -> Option<String> {
+    let line_start: usize = input
+        .split_inclusive('\n')
+        .take(position.line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let end = line_start
+        + input[line_start..]
+            .chars()
+            .take(position.col.saturating_sub(1))
+            .map(char::len_utf8)
+            .sum::<usize>();
+    let start = end.checked_sub(name.len())?;
+
+    (input.get(start..end)? == name).then(|| format!("{}{}", &input[..start], &input[end..]))
+}
@@ -0,0 +1,121 @@
+//! Validate settings files against a JSON Schema.
+
+use std::path::Path;
+
+use serde_json::{Map, Number as JsonNumber, Value as Json};
+
+use crate::{
+    parse, read,
+    value::{Number, Value},
+    Error,
+};
+
+/// Check the settings file at `path` against a JSON Schema.
+///
+/// The file is parsed as RON and converted to JSON, where structs and maps
+/// become objects, sequences and tuples become arrays, `None` and `()` become
+/// `null`, and `Some` values are unwrapped. Unit enum variants become their
+/// name as a string and other variants an object with the variant name as
+/// its only key, the way JSON files store them. Each violation is reported
+/// as one entry of [`Error::SchemaValidation`], prefixed with the JSON
+/// pointer of the offending value. A schema that is itself invalid, or a
+/// file with map keys that are not strings, numbers or booleans, is reported
+/// the same way.
+///
+/// ```rust
+/// # use settings::{validate_against_schema, Error};
+/// # use tempfile::tempdir;
+/// # use std::{error, fs};
+/// # fn main() -> Result<(), Box<dyn error::Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.ron");
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "required": ["foo", "bar"],
+///     "properties": {
+///         "bar": { "type": "integer" },
+///         "theme": { "enum": ["Light", "Dark"] },
+///     },
+/// });
+///
+/// fs::write(&path, r#"(foo: "Hello World", bar: 1, theme: Dark)"#)?;
+/// validate_against_schema(&path, &schema)?;
+///
+/// fs::write(&path, r#"(bar: 1, theme: Blue)"#)?;
+/// match validate_against_schema(&path, &schema) {
+///     Err(Error::SchemaValidation { errors }) => {
+///         assert_eq!(errors, vec![
+///             r#""foo" is a required property"#.to_string(),
+///             r#"/theme: "Blue" is not one of "Light" or "Dark""#.to_string(),
+///         ]);
+///     }
+///     result => panic!("unexpected result {:?}", result),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn validate_against_schema(path: &Path, schema: &Json) -> Result<(), Error> {
+    let invalid = |err: &dyn std::fmt::Display| Error::SchemaValidation {
+        errors: vec![err.to_string()],
+    };
+
+    // A struct name at the root, like `Config(..)`, is not a variant.
+    let value = match parse::from_bytes(&read(path)?)? {
+        Value::Named(_, fields) if matches!(*fields, Value::Map(_)) => *fields,
+        value => value,
+    };
+    let instance = to_json(value).map_err(|err| invalid(&err))?;
+    let validator = jsonschema::validator_for(schema).map_err(|err| invalid(&err))?;
+
+    let errors: Vec<_> = validator
+        .iter_errors(&instance)
+        .map(|err| match err.instance_path().as_str() {
+            "" => err.to_string(),
+            pointer => format!("{}: {}", pointer, err),
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::SchemaValidation { errors })
+    }
+}
+
+/// Convert an untyped value to JSON.
+fn to_json(value: Value) -> Result<Json, String> {
+    Ok(match value {
+        Value::Unit | Value::Option(None) => Json::Null,
+        Value::Bool(bool) => Json::Bool(bool),
+        Value::Number(Number::I64(integer)) => Json::from(integer),
+        Value::Number(Number::U64(integer)) => Json::from(integer),
+        Value::Number(Number::F64(float)) => {
+            JsonNumber::from_f64(float).map_or(Json::Null, Json::Number)
+        }
+        Value::Char(char) => Json::String(char.to_string()),
+        Value::String(string) => Json::String(string),
+        Value::Option(Some(value)) => to_json(*value)?,
+        Value::Seq(values) | Value::Tuple(values) => {
+            Json::Array(values.into_iter().map(to_json).collect::<Result<_, _>>()?)
+        }
+        Value::Map(entries) => {
+            let mut map = Map::new();
+            for (key, value) in entries {
+                let key = match key {
+                    Value::String(string) => string,
+                    Value::Char(char) => char.to_string(),
+                    key @ (Value::Number(_) | Value::Bool(_)) => key.to_string(),
+                    key => return Err(format!("map key {} is not a string", key)),
+                };
+                map.insert(key, to_json(value)?);
+            }
+            Json::Object(map)
+        }
+        Value::Named(variant, content) => match *content {
+            Value::Unit => Json::String(variant),
+            Value::Tuple(mut values) if values.len() == 1 => {
+                Json::Object(Map::from_iter([(variant, to_json(values.remove(0))?)]))
+            }
+            content => Json::Object(Map::from_iter([(variant, to_json(content)?)])),
+        },
+    })
+}
@@ -0,0 +1,589 @@
+//! Serializers that look at the top-level fields of a value.
+
+use serde::{
+    ser::{self, Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeTuple},
+    Serialize, Serializer,
+};
+
+/// The name and compact RON representation of each top-level field.
+type Fields = Vec<(String, String)>;
+
+/// Serialize each top-level field of a struct, entry of a map or element of a
+/// sequence on its own. Elements are named by their index.
+///
+/// Unlike going through [`ron::Value`], this keeps enum variants and struct
+/// names intact.
+pub(crate) fn fields<T>(value: &T) -> Result<Fields, ron::Error>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(FieldSerializer)
+}
+
+/// The name of a map key, without quotes for plain strings.
+fn key_name(key: String) -> String {
+    ron::from_str::<String>(&key).unwrap_or(key)
+}
+
+fn not_a_struct() -> ron::Error {
+    ser::Error::custom("expected a struct, map or sequence")
+}
+
+struct FieldSerializer;
+
+#[derive(Default)]
+struct Collect {
+    fields: Fields,
+    key: Option<String>,
+}
+
+macro_rules! not_a_struct {
+    ($($method:ident($($arg:ty),*);)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<Self::Ok, Self::Error> {
+                Err(not_a_struct())
+            }
+        )*
+    };
+}
+
+impl Serializer for FieldSerializer {
+    type Ok = Fields;
+    type Error = ron::Error;
+    type SerializeSeq = Collect;
+    type SerializeTuple = Collect;
+    type SerializeTupleStruct = Impossible<Fields, ron::Error>;
+    type SerializeTupleVariant = Impossible<Fields, ron::Error>;
+    type SerializeMap = Collect;
+    type SerializeStruct = Collect;
+    type SerializeStructVariant = Impossible<Fields, ron::Error>;
+
+    not_a_struct! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(not_a_struct())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(Collect::default())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Ok(Collect::default())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(not_a_struct())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(Collect::default())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(Collect::default())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(not_a_struct())
+    }
+}
+
+impl SerializeStruct for Collect {
+    type Ok = Fields;
+    type Error = ron::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.fields.push((key.to_string(), ron::to_string(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.fields)
+    }
+}
+
+impl SerializeSeq for Collect {
+    type Ok = Fields;
+    type Error = ron::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let index = self.fields.len().to_string();
+        self.fields.push((index, ron::to_string(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.fields)
+    }
+}
+
+impl SerializeTuple for Collect {
+    type Ok = Fields;
+    type Error = ron::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl SerializeMap for Collect {
+    type Ok = Fields;
+    type Error = ron::Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key = Some(key_name(ron::to_string(key)?));
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self.key.take().unwrap_or_default();
+        self.fields.push((key, ron::to_string(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.fields)
+    }
+}
+
+/// A predicate deciding whether to keep a top-level field, given its name and
+/// its compact RON representation.
+pub(crate) type Keep<'a> = &'a dyn Fn(&str, &str) -> bool;
+
+/// Settings with only some of their top-level fields, as written by
+/// [`Settings::save_minimal`](crate::Settings::save_minimal) and
+/// [`Settings::export_fields`](crate::Settings::export_fields).
+///
+/// Serializing it produces exactly what serializing the settings would,
+/// minus the dropped fields, so any serde format can write it. See
+/// [`Codec::serialize_partial`](crate::Codec::serialize_partial).
+pub struct Partial<'a, T: ?Sized> {
+    pub(crate) value: &'a T,
+    pub(crate) keep: Keep<'a>,
+    /// Whether the elements of a top-level sequence are filtered as well,
+    /// named by their index; otherwise a sequence is written whole.
+    pub(crate) elements: bool,
+}
+
+impl<T> Serialize for Partial<'_, T>
+where
+    T: Serialize + ?Sized,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.value.serialize(FilterSerializer {
+            inner: serializer,
+            keep: self.keep,
+            elements: self.elements,
+        })
+    }
+}
+
+struct FilterSerializer<'a, S> {
+    inner: S,
+    keep: Keep<'a>,
+    elements: bool,
+}
+
+struct FilterCompound<'a, C> {
+    inner: C,
+    keep: Keep<'a>,
+    key: Option<String>,
+    index: usize,
+}
+
+impl<'a, C> FilterCompound<'a, C> {
+    fn new(inner: C, keep: Keep<'a>) -> Self {
+        Self {
+            inner,
+            keep,
+            key: None,
+            index: 0,
+        }
+    }
+
+    /// Whether to keep the next sequence element, `value`.
+    fn keep_element<T, E>(&mut self, value: &T) -> Result<bool, E>
+    where
+        T: Serialize + ?Sized,
+        E: ser::Error,
+    {
+        let index = self.index.to_string();
+        self.index += 1;
+        Ok((self.keep)(&index, &compact::<_, E>(value)?))
+    }
+}
+
+fn compact<T, E>(value: &T) -> Result<String, E>
+where
+    T: Serialize + ?Sized,
+    E: ser::Error,
+{
+    ron::to_string(value).map_err(E::custom)
+}
+
+macro_rules! delegate {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<Self::Ok, Self::Error> {
+                self.inner.$method($($arg),*)
+            }
+        )*
+    };
+}
+
+impl<'a, S> Serializer for FilterSerializer<'a, S>
+where
+    S: Serializer,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = FilterCompound<'a, S::SerializeSeq>;
+    type SerializeTuple = FilterCompound<'a, S::SerializeTuple>;
+    type SerializeTupleStruct = S::SerializeTupleStruct;
+    type SerializeTupleVariant = S::SerializeTupleVariant;
+    type SerializeMap = FilterCompound<'a, S::SerializeMap>;
+    type SerializeStruct = FilterCompound<'a, S::SerializeStruct>;
+    type SerializeStructVariant = S::SerializeStructVariant;
+
+    delegate! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i64(v: i64);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_f32(v: f32);
+        serialize_f64(v: f64);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str);
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let (keep, elements) = (self.keep, self.elements);
+        self.inner.serialize_some(&Partial {
+            value,
+            keep,
+            elements,
+        })
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let (keep, elements) = (self.keep, self.elements);
+        self.inner.serialize_newtype_struct(
+            name,
+            &Partial {
+                value,
+                keep,
+                elements,
+            },
+        )
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.inner
+            .serialize_newtype_variant(name, variant_index, variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        let keep: Keep<'a> = if self.elements { self.keep } else { &keep_all };
+        Ok(FilterCompound::new(self.inner.serialize_seq(len)?, keep))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        let keep: Keep<'a> = if self.elements { self.keep } else { &keep_all };
+        Ok(FilterCompound::new(self.inner.serialize_tuple(len)?, keep))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.inner.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        self.inner
+            .serialize_tuple_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(FilterCompound::new(
+            self.inner.serialize_map(len)?,
+            self.keep,
+        ))
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(FilterCompound::new(
+            self.inner.serialize_struct(name, len)?,
+            self.keep,
+        ))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        self.inner
+            .serialize_struct_variant(name, variant_index, variant, len)
+    }
+}
+
+/// Keeps every field.
+fn keep_all(_: &str, _: &str) -> bool {
+    true
+}
+
+impl<C> SerializeSeq for FilterCompound<'_, C>
+where
+    C: SerializeSeq,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        if self.keep_element::<_, C::Error>(value)? {
+            self.inner.serialize_element(value)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C> SerializeTuple for FilterCompound<'_, C>
+where
+    C: SerializeTuple,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        if self.keep_element::<_, C::Error>(value)? {
+            self.inner.serialize_element(value)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C> SerializeStruct for FilterCompound<'_, C>
+where
+    C: SerializeStruct,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        if (self.keep)(key, &compact::<_, C::Error>(value)?) {
+            self.inner.serialize_field(key, value)
+        } else {
+            self.inner.skip_field(key)
+        }
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
+
+impl<C> SerializeMap for FilterCompound<'_, C>
+where
+    C: SerializeMap,
+{
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.key = Some(compact::<_, C::Error>(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        // The key is only known as RON text here, so it is written back
+        // through `ron::Value`.
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        if (self.keep)(&key_name(key.clone()), &compact::<_, C::Error>(value)?) {
+            let key: ron::Value = ron::from_str(&key).map_err(ser::Error::custom)?;
+            self.inner.serialize_entry(&key, value)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), Self::Error>
+    where
+        K: Serialize + ?Sized,
+        V: Serialize + ?Sized,
+    {
+        let name = key_name(compact::<_, C::Error>(key)?);
+        if (self.keep)(&name, &compact::<_, C::Error>(value)?) {
+            self.inner.serialize_entry(key, value)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.inner.end()
+    }
+}
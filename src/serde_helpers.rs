@@ -0,0 +1,178 @@
+//! Helpers for `#[serde(with = "...")]` that keep settings files readable.
+//!
+//! ```rust
+//! # use settings::Settings;
+//! # use tempfile::tempdir;
+//! # use std::{error::Error, fs, path::PathBuf, time::Duration};
+//! use serde::{Deserialize, Serialize};
+//! use settings::serde_helpers;
+//!
+//! #[derive(Debug, Clone, Deserialize, Serialize)]
+//! struct Config {
+//!     #[serde(with = "serde_helpers::duration_secs")]
+//!     pub timeout: Duration,
+//!     #[serde(with = "serde_helpers::duration_millis")]
+//!     pub poll_interval: Duration,
+//!     #[serde(with = "serde_helpers::path_from_str")]
+//!     pub cache: PathBuf,
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! # let dir = tempdir()?;
+//! # let path = dir.path().join("settings.ron");
+//! fs::write(&path, r#"(timeout: 30, poll_interval: 250, cache: "/tmp/cache")"#)?;
+//!
+//! let settings = Settings::<Config>::load_from(&path)?;
+//! assert_eq!(settings.timeout, Duration::from_secs(30));
+//! assert_eq!(settings.poll_interval, Duration::from_millis(250));
+//! assert_eq!(settings.cache, PathBuf::from("/tmp/cache"));
+//!
+//! settings.save()?;
+//! assert_eq!(fs::read_to_string(&path)?, r#"(
+//!     timeout: 30,
+//!     poll_interval: 250,
+//!     cache: "/tmp/cache",
+//! )"#);
+//! # Ok(())
+//! # }
+//! ```
+
+/// Store a [`Duration`](std::time::Duration) as a whole number of seconds.
+///
+/// Fractions of a second are dropped when saving.
+pub mod duration_secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(duration.as_secs())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
+
+/// Store a [`Duration`](std::time::Duration) as a whole number of milliseconds.
+///
+/// Fractions of a millisecond are dropped when saving.
+pub mod duration_millis {
+    use std::time::Duration;
+
+    use serde::{ser::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let millis = u64::try_from(duration.as_millis())
+            .map_err(|_| S::Error::custom("duration is too long"))?;
+        serializer.serialize_u64(millis)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+/// Store a [`PathBuf`](std::path::PathBuf) as a plain string.
+///
+/// Saving fails for paths that are not valid UTF-8.
+pub mod path_from_str {
+    use std::path::{Path, PathBuf};
+
+    use serde::{ser::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S, P>(path: P, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        P: AsRef<Path>,
+    {
+        let path = path
+            .as_ref()
+            .to_str()
+            .ok_or_else(|| S::Error::custom("path is not valid UTF-8"))?;
+        serializer.serialize_str(path)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<PathBuf, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(PathBuf::from)
+    }
+}
+
+/// Store a [`HashMap`](std::collections::HashMap) with its keys in sorted order.
+///
+/// A `HashMap` iterates in a different order every time the program runs, so
+/// saving one directly produces a different file each time. With this helper
+/// the output is the same as for a `BTreeMap` with the same entries.
+///
+/// ```rust
+/// # use settings::Settings;
+/// # use tempfile::tempdir;
+/// # use std::{collections::HashMap, error::Error, fs};
+/// use serde::{Deserialize, Serialize};
+/// use settings::serde_helpers;
+///
+/// #[derive(Debug, Clone, Deserialize, Serialize)]
+/// struct Config {
+///     #[serde(with = "serde_helpers::sorted_map")]
+///     pub aliases: HashMap<String, String>,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// let mut outputs = Vec::new();
+/// for i in 0..2 {
+///     let aliases = ["st", "co", "br", "ci"]
+///         .into_iter()
+///         .map(|alias| (alias.to_string(), format!("git {}", alias)))
+///         .collect();
+///     let path = dir.path().join(format!("{}.ron", i));
+///     Settings::new(Config { aliases }, &path).save()?;
+///     outputs.push(fs::read_to_string(&path)?);
+/// }
+/// assert_eq!(outputs[0], outputs[1]);
+/// assert!(outputs[0].starts_with("(\n    aliases: {\n        \"br\""));
+/// # Ok(())
+/// # }
+/// ```
+pub mod sorted_map {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        hash::{BuildHasher, Hash},
+    };
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S, K, V, H>(map: &HashMap<K, V, H>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        K: Serialize + Ord,
+        V: Serialize,
+    {
+        map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, K, V, H>(deserializer: D) -> Result<HashMap<K, V, H>, D::Error>
+    where
+        D: Deserializer<'de>,
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        H: BuildHasher + Default,
+    {
+        HashMap::deserialize(deserializer)
+    }
+}
@@ -0,0 +1,187 @@
+//! Share settings between threads, replacing them on every change.
+
+use std::{
+    fmt::Debug,
+    mem,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Error, Settings};
+
+/// Settings shared between threads as immutable snapshots.
+///
+/// Readers get the current settings as an [`Arc<T>`](Arc) from
+/// [`snapshot`](Self::snapshot), which is cheap and never waits for a save.
+/// Writers go through [`update`](Self::update), which copies the current
+/// settings, changes and saves the copy, and then swaps it in for new
+/// snapshots. A snapshot never changes, so a reader holding one keeps seeing
+/// consistent settings even while an update happens; it only sees the update
+/// when it takes a new snapshot.
+///
+/// Cloning the handle is cheap and all clones share the same settings. Every
+/// old snapshot stays in memory until its last reader drops it, and one more
+/// copy of `T` is kept for saving.
+///
+/// ```rust
+/// # use settings::{ArcSettings, Settings};
+/// # use tempfile::tempdir;
+/// # use std::{error::Error, fs, thread};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Debug, Clone, Deserialize, Serialize)]
+/// struct Config {
+///     pub width: u32,
+///     pub height: u32,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.ron");
+/// fs::write(&path, "(width: 0, height: 0)")?;
+/// let settings = ArcSettings::new(Settings::<Config>::load_from(&path)?);
+///
+/// let readers: Vec<_> = (0..4)
+///     .map(|_| {
+///         let settings = settings.clone();
+///         thread::spawn(move || {
+///             for _ in 0..1000 {
+///                 let config = settings.snapshot();
+///                 assert_eq!(config.width * 2, config.height);
+///             }
+///         })
+///     })
+///     .collect();
+///
+/// for size in 1..=100 {
+///     settings.update(|config| {
+///         config.width = size;
+///         config.height = size * 2;
+///     })?;
+/// }
+/// for reader in readers {
+///     reader.join().unwrap();
+/// }
+///
+/// assert_eq!(settings.snapshot().width, 100);
+/// assert_eq!(Settings::<Config>::load_from(&path)?.height, 200);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArcSettings<T> {
+    shared: Arc<Shared<T>>,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    /// The snapshot handed to readers.
+    current: RwLock<Arc<T>>,
+
+    /// The settings that are saved, also serializing updates.
+    settings: Mutex<Settings<T>>,
+}
+
+impl<T> ArcSettings<T>
+where
+    T: Debug + Clone + Serialize + DeserializeOwned,
+{
+    /// Share `settings`.
+    pub fn new(settings: Settings<T>) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                current: RwLock::new(Arc::new(settings.inner.clone())),
+                settings: Mutex::new(settings),
+            }),
+        }
+    }
+
+    /// The current settings.
+    pub fn snapshot(&self) -> Arc<T> {
+        self.shared
+            .current
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Change the settings with `f` and save them.
+    ///
+    /// `f` gets a copy of the current settings. If saving the settings file
+    /// fails, the change is discarded and the error is returned; otherwise
+    /// the copy becomes the current settings. Since the file already holds
+    /// the change when only [mirrors](Settings::add_mirror) fail, the copy
+    /// becomes the current settings in that case as well, before
+    /// [`Error::Mirror`] is returned. Updates from several threads run one at
+    /// a time.
+    ///
+    /// ```rust
+    /// # use settings::{ArcSettings, Error, Settings};
+    /// # use tempfile::tempdir;
+    /// # use std::{error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// # fs::write(&path, r#"(foo: "")"#)?;
+    /// let mut settings = Settings::<Config>::load_from(&path)?;
+    /// settings.add_mirror(dir.path().join("missing").join("settings.ron"));
+    /// let settings = ArcSettings::new(settings);
+    ///
+    /// let result = settings.update(|config| config.foo = "saved".to_string());
+    /// assert!(matches!(result, Err(Error::Mirror { .. })));
+    /// assert_eq!(settings.snapshot().foo, "saved");
+    ///
+    /// fs::remove_file(&path)?;
+    /// fs::create_dir(&path)?;
+    /// let result = settings.update(|config| config.foo = "lost".to_string());
+    /// assert!(matches!(result, Err(Error::Open { .. })));
+    /// assert_eq!(settings.snapshot().foo, "saved");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update<F>(&self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut T),
+    {
+        let mut settings = self
+            .shared
+            .settings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let mut next = settings.inner.clone();
+        f(&mut next);
+        let previous = mem::replace(&mut settings.inner, next);
+        let result = settings.save();
+        if let Err(err) = &result {
+            if !matches!(err, Error::Mirror { .. }) {
+                settings.inner = previous;
+                return result;
+            }
+        }
+
+        let next = Arc::new(settings.inner.clone());
+        *self
+            .shared
+            .current
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = next;
+        result
+    }
+
+    /// The path the settings are saved to.
+    pub fn path(&self) -> PathBuf {
+        self.shared
+            .settings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .path()
+            .to_path_buf()
+    }
+}
@@ -0,0 +1,183 @@
+//! Store settings as TOML, keeping comments and formatting on save.
+
+use serde::{de::DeserializeOwned, Serialize};
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+use crate::{Codec, Error, Partial};
+
+/// A codec storing settings as TOML.
+///
+/// When the settings file already exists, saving patches it instead of
+/// rewriting it: values that did not change are left exactly as written,
+/// changed values keep the comments around them, and only keys that were
+/// added or removed change the layout. Arrays of tables are replaced as a
+/// whole when they change.
+///
+/// ```rust
+/// # use settings::{Settings, Toml};
+/// # use tempfile::tempdir;
+/// # use std::{error::Error, fs};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Debug, Clone, Deserialize, Serialize)]
+/// struct Server {
+///     pub host: String,
+///     pub port: u16,
+/// }
+///
+/// #[derive(Debug, Clone, Deserialize, Serialize)]
+/// struct Config {
+///     pub name: String,
+///     pub server: Server,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.toml");
+/// fs::write(&path, r#"# The name shown in the title bar
+/// name = "demo"
+///
+/// ## Where to listen
+/// [server]
+/// host = "localhost" # only local connections
+/// port = 8080 # the default port
+/// "#)?;
+///
+/// let mut settings = Settings::<Config>::with_codec(Toml).load_from(&path)?;
+/// settings.server.port = 9090;
+/// settings.save()?;
+///
+/// assert_eq!(fs::read_to_string(&path)?, r#"# The name shown in the title bar
+/// name = "demo"
+///
+/// ## Where to listen
+/// [server]
+/// host = "localhost" # only local connections
+/// port = 9090 # the default port
+/// "#);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Methods that work on untyped values, like
+/// [`Settings::modify_field_locked`](crate::Settings::modify_field_locked) or [merging](crate::ConflictStrategy::Merge)
+/// several files, read TOML as well.
+///
+/// ```rust
+/// # use settings::{Settings, Toml};
+/// # use tempfile::tempdir;
+/// # use std::{error::Error, fs};
+/// # use serde::{Deserialize, Serialize};
+/// use ron::{Number, Value};
+///
+/// #[derive(Debug, Clone, Deserialize, Serialize)]
+/// struct Config {
+///     pub launch_count: u32,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// # let dir = tempdir()?;
+/// # let path = dir.path().join("settings.toml");
+/// fs::write(&path, "launch_count = 1 # bumped on start\nremoved_in_v2 = true\n")?;
+///
+/// let mut settings = Settings::<Config>::with_codec(Toml).load_from(&path)?;
+/// assert_eq!(settings.prune_file()?, vec!["removed_in_v2".to_string()]);
+///
+/// settings.modify_field_locked("launch_count", |value| {
+///     if let Value::Number(Number::Integer(count)) = value {
+///         *count += 1;
+///     }
+/// })?;
+/// assert_eq!(settings.launch_count, 2);
+/// assert_eq!(fs::read_to_string(&path)?, "launch_count = 2 # bumped on start\n");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Toml;
+
+impl<T> Codec<T> for Toml
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn deserialize(&self, bytes: &[u8]) -> Result<T, Error> {
+        toml_edit::de::from_slice(bytes).map_err(|err| Error::Codec(err.into()))
+    }
+
+    fn deserialize_value(&self, bytes: &[u8]) -> Result<ron::Value, Error> {
+        toml_edit::de::from_slice(bytes).map_err(|err| Error::Codec(err.into()))
+    }
+
+    fn serialize(&self, value: &T) -> Result<Vec<u8>, Error> {
+        toml_edit::ser::to_string_pretty(value)
+            .map(String::into_bytes)
+            .map_err(|err| Error::Codec(err.into()))
+    }
+
+    fn serialize_partial(&self, value: &Partial<'_, T>) -> Result<Vec<u8>, Error> {
+        toml_edit::ser::to_string_pretty(value)
+            .map(String::into_bytes)
+            .map_err(|err| Error::Codec(err.into()))
+    }
+
+    fn update(&self, previous: &[u8], value: &T) -> Result<Vec<u8>, Error> {
+        let contents = Codec::<T>::serialize(self, value)?;
+        let previous = std::str::from_utf8(previous)
+            .ok()
+            .and_then(|previous| previous.parse::<DocumentMut>().ok());
+        let Some(mut document) = previous else {
+            return Ok(contents);
+        };
+
+        let current = String::from_utf8_lossy(&contents)
+            .parse::<DocumentMut>()
+            .map_err(|err| Error::Codec(err.into()))?;
+        patch(document.as_table_mut(), current.as_table());
+        Ok(document.to_string().into_bytes())
+    }
+
+    fn format(&self) -> &'static str {
+        "toml"
+    }
+}
+
+/// Make `table` hold the same values as `current`, keeping its formatting
+/// wherever the values did not change.
+fn patch(table: &mut Table, current: &Table) {
+    table.retain(|key, _| current.contains_key(key));
+    for (key, item) in current.iter() {
+        match (table.get_mut(key), item) {
+            (Some(Item::Table(table)), Item::Table(current)) => patch(table, current),
+            (Some(Item::Value(value)), Item::Value(current)) => {
+                if !same(value, current) {
+                    let decor = value.decor().clone();
+                    *value = current.clone();
+                    *value.decor_mut() = decor;
+                }
+            }
+            (Some(previous), item) => *previous = item.clone(),
+            (None, item) => {
+                table.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+/// Whether `a` and `b` hold the same value, ignoring formatting.
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Integer(a), Value::Integer(b)) => a.value() == b.value(),
+        (Value::Float(a), Value::Float(b)) => a.value() == b.value(),
+        (Value::Boolean(a), Value::Boolean(b)) => a.value() == b.value(),
+        (Value::Datetime(a), Value::Datetime(b)) => a.value() == b.value(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same(a, b))
+        }
+        (Value::InlineTable(a), Value::InlineTable(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| same(a, b)))
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,122 @@
+//! Spans around loading and saving, recorded with the `tracing` feature.
+//!
+//! Without the feature every operation here is a no-op.
+//!
+//! ```rust
+//! # #[cfg(feature = "tracing")]
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # use settings::Settings;
+//! # use tempfile::tempdir;
+//! # use std::{fmt, fs, sync::{Arc, Mutex}};
+//! # use tracing::{field::{Field, Visit}, span::{Attributes, Id, Record}, Subscriber};
+//! # use tracing_subscriber::{layer::{Context, Layer, SubscriberExt}, registry::Registry};
+//! # #[derive(Clone, Default)]
+//! # struct Capture(Arc<Mutex<Vec<String>>>);
+//! # impl Visit for Capture {
+//! #     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+//! #         self.0.lock().unwrap().push(format!("{}={:?}", field.name(), value));
+//! #     }
+//! # }
+//! # impl<S: Subscriber> Layer<S> for Capture {
+//! #     fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+//! #         self.0.lock().unwrap().push(attrs.metadata().name().to_string());
+//! #         attrs.record(&mut self.clone());
+//! #     }
+//! #     fn on_record(&self, _: &Id, values: &Record<'_>, _: Context<'_, S>) {
+//! #         values.record(&mut self.clone());
+//! #     }
+//! # }
+//! # let capture = Capture::default();
+//! # let _guard = tracing::subscriber::set_default(Registry::default().with(capture.clone()));
+//! # let dir = tempdir()?;
+//! # let path = dir.path().join("settings.ron");
+//! fs::write(&path, "(answer: 42)")?;
+//!
+//! # #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+//! # struct Config { answer: u32 }
+//! let settings = Settings::<Config>::load_from(&path)?;
+//! settings.save()?;
+//!
+//! let fields = capture.0.lock().unwrap().join(" ");
+//! assert!(fields.contains("settings.load"));
+//! assert!(fields.contains("settings.save"));
+//! assert!(fields.contains(&format!("path={}", path.display())));
+//! assert!(fields.contains("format=\"ron\""));
+//! assert!(fields.contains("bytes=12"));
+//! assert!(fields.contains("duration_us="));
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "tracing"))]
+//! # fn main() {}
+//! ```
+
+use std::path::Path;
+
+/// A span covering a single load or save.
+pub(crate) struct Span {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+    #[cfg(feature = "tracing")]
+    start: std::time::Instant,
+}
+
+impl Span {
+    /// Enter a span for loading the file at `path`.
+    pub(crate) fn load(path: &Path, format: &str) -> Self {
+        #[cfg(not(feature = "tracing"))]
+        let _ = (path, format);
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "settings.load",
+            path = %path.display(),
+            format,
+            bytes = tracing::field::Empty,
+            duration_us = tracing::field::Empty,
+        );
+        Self::enter(
+            #[cfg(feature = "tracing")]
+            span,
+        )
+    }
+
+    /// Enter a span for saving to the file at `path`.
+    pub(crate) fn save(path: &Path, format: &str) -> Self {
+        #[cfg(not(feature = "tracing"))]
+        let _ = (path, format);
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "settings.save",
+            path = %path.display(),
+            format,
+            bytes = tracing::field::Empty,
+            duration_us = tracing::field::Empty,
+        );
+        Self::enter(
+            #[cfg(feature = "tracing")]
+            span,
+        )
+    }
+
+    fn enter(#[cfg(feature = "tracing")] span: tracing::Span) -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            span: span.entered(),
+            #[cfg(feature = "tracing")]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    /// Record the size of the file that was read or written.
+    pub(crate) fn record_bytes(&self, _bytes: usize) {
+        #[cfg(feature = "tracing")]
+        self.span.record("bytes", _bytes);
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for Span {
+    fn drop(&mut self) {
+        let duration = self.start.elapsed().as_micros() as u64;
+        self.span.record("duration_us", duration);
+    }
+}
@@ -0,0 +1,539 @@
+//! Untyped settings values that keep enum variants and struct names.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_path_to_error::Segment;
+
+use crate::{parse, Codec, Error};
+
+/// An untyped value.
+///
+/// Unlike [`ron::Value`], which drops every identifier, this keeps enum
+/// variants and struct names, so it deserializes back into the type it was
+/// serialized from.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Unit,
+    Bool(bool),
+    Number(Number),
+    Char(char),
+    String(String),
+    Option(Option<Box<Value>>),
+
+    /// A sequence in brackets.
+    Seq(Vec<Value>),
+
+    /// A tuple in parentheses, which also holds the content of newtype
+    /// structs and variants.
+    Tuple(Vec<Value>),
+
+    /// A map or the fields of a struct, in order.
+    Map(Vec<(Value, Value)>),
+
+    /// An enum variant or a struct with its name, holding [`Value::Unit`], a
+    /// [`Value::Tuple`] or the fields in a [`Value::Map`].
+    Named(String, Box<Value>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Number {
+    I64(i64),
+    U64(u64),
+    F64(f64),
+}
+
+impl Value {
+    /// Deserialize the value into `T`.
+    pub(crate) fn into_rust<T>(self) -> Result<T, ron::Error>
+    where
+        T: DeserializeOwned,
+    {
+        T::deserialize(self)
+    }
+}
+
+impl From<ron::Value> for Value {
+    fn from(value: ron::Value) -> Self {
+        match value {
+            ron::Value::Bool(bool) => Value::Bool(bool),
+            ron::Value::Char(char) => Value::Char(char),
+            ron::Value::Map(map) => Value::Map(
+                map.into_iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            ),
+            ron::Value::Number(ron::Number::Integer(integer)) => {
+                Value::Number(Number::I64(integer))
+            }
+            ron::Value::Number(ron::Number::Float(float)) => {
+                Value::Number(Number::F64(float.get()))
+            }
+            ron::Value::Option(option) => {
+                Value::Option(option.map(|value| Box::new((*value).into())))
+            }
+            ron::Value::String(string) => Value::String(string),
+            ron::Value::Seq(seq) => Value::Seq(seq.into_iter().map(Value::from).collect()),
+            ron::Value::Unit => Value::Unit,
+        }
+    }
+}
+
+/// Enums are written the way other formats store them: unit variants as
+/// strings and other variants as maps with a single entry, named after the
+/// variant. Newtypes are written as their content. [`Value`] deserializes all
+/// of these back.
+impl From<Value> for ron::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Unit => ron::Value::Unit,
+            Value::Bool(bool) => ron::Value::Bool(bool),
+            Value::Number(Number::I64(integer)) => ron::Value::Number(integer.into()),
+            Value::Number(Number::U64(integer)) => match i64::try_from(integer) {
+                Ok(integer) => ron::Value::Number(integer.into()),
+                Err(_) => ron::Value::Number((integer as f64).into()),
+            },
+            Value::Number(Number::F64(float)) => ron::Value::Number(float.into()),
+            Value::Char(char) => ron::Value::Char(char),
+            Value::String(string) => ron::Value::String(string),
+            Value::Option(option) => {
+                ron::Value::Option(option.map(|value| Box::new((*value).into())))
+            }
+            Value::Tuple(mut tuple) if tuple.len() == 1 => tuple.remove(0).into(),
+            Value::Seq(seq) | Value::Tuple(seq) => {
+                ron::Value::Seq(seq.into_iter().map(ron::Value::from).collect())
+            }
+            Value::Map(map) => ron::Value::Map(
+                map.into_iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            ),
+            Value::Named(name, content) => match *content {
+                Value::Unit => ron::Value::String(name),
+                content => ron::Value::Map(
+                    [(ron::Value::String(name), content.into())]
+                        .into_iter()
+                        .collect(),
+                ),
+            },
+        }
+    }
+}
+
+/// Writes the value as compact RON.
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list(f: &mut fmt::Formatter<'_>, values: &[Value]) -> fmt::Result {
+            for (index, value) in values.iter().enumerate() {
+                if index > 0 {
+                    f.write_str(",")?;
+                }
+                write!(f, "{}", value)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Value::Unit => f.write_str("()"),
+            Value::Bool(bool) => write!(f, "{}", bool),
+            Value::Number(Number::I64(integer)) => write!(f, "{}", integer),
+            Value::Number(Number::U64(integer)) => write!(f, "{}", integer),
+            Value::Number(Number::F64(float)) if float.fract() == 0.0 => write!(f, "{}.0", float),
+            Value::Number(Number::F64(float)) => write!(f, "{}", float),
+            Value::Char(char) => f.write_str(&ron::to_string(char).map_err(|_| fmt::Error)?),
+            Value::String(string) => f.write_str(&ron::to_string(string).map_err(|_| fmt::Error)?),
+            Value::Option(None) => f.write_str("None"),
+            Value::Option(Some(value)) => write!(f, "Some({})", value),
+            Value::Seq(seq) => {
+                f.write_str("[")?;
+                list(f, seq)?;
+                f.write_str("]")
+            }
+            Value::Tuple(tuple) => {
+                f.write_str("(")?;
+                list(f, tuple)?;
+                f.write_str(")")
+            }
+            Value::Map(map) => {
+                f.write_str("{")?;
+                for (index, (key, value)) in map.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}:{}", key, value)?;
+                }
+                f.write_str("}")
+            }
+            Value::Named(name, content) => match &**content {
+                Value::Unit => f.write_str(name),
+                Value::Map(fields) => {
+                    write!(f, "{}(", name)?;
+                    for (index, (key, value)) in fields.iter().enumerate() {
+                        if index > 0 {
+                            f.write_str(",")?;
+                        }
+                        write!(f, "{}:{}", key_name(key), value)?;
+                    }
+                    f.write_str(")")
+                }
+                content => write!(f, "{}{}", name, content),
+            },
+        }
+    }
+}
+
+/// Serialize `value` into an untyped [`Value`].
+pub(crate) fn to_value<T>(value: &T) -> Result<Value, Error>
+where
+    T: Serialize + ?Sized,
+{
+    let string = ron::to_string(value).map_err(Error::Serialize)?;
+    parse::from_str(&string).map_err(|err| Error::Value(err.code))
+}
+
+/// Parse the contents of a settings file into an untyped [`Value`].
+///
+/// RON is parsed by this crate, since [`ron::Value`] drops enum variants;
+/// other formats go through [`Codec::deserialize_value`].
+pub(crate) fn from_file<T>(codec: &dyn Codec<T>, bytes: &[u8]) -> Result<Value, Error> {
+    if codec.format() == "ron" {
+        parse::from_bytes(bytes)
+    } else {
+        codec.deserialize_value(bytes).map(Value::from)
+    }
+}
+
+/// The fields of `value`, looking through a struct name.
+fn fields(value: &Value) -> Option<&Vec<(Value, Value)>> {
+    match value {
+        Value::Map(map) => Some(map),
+        Value::Named(_, content) => match &**content {
+            Value::Map(map) => Some(map),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The fields of `value`, looking through a struct name.
+fn fields_mut(value: &mut Value) -> Option<&mut Vec<(Value, Value)>> {
+    match value {
+        Value::Map(map) => Some(map),
+        Value::Named(_, content) => match &mut **content {
+            Value::Map(map) => Some(map),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Lay `overlay` over `base`.
+///
+/// Maps are merged key by key, recursing into nested maps, whether or not
+/// structs are written with their names. Any other value in `overlay`,
+/// including a different enum variant, replaces the corresponding value in
+/// `base`.
+pub(crate) fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Named(name, base), Value::Named(other, overlay)) if *name == other => {
+            merge(base, *overlay)
+        }
+        (Value::Named(_, base), overlay @ Value::Map(_)) if matches!(**base, Value::Map(_)) => {
+            merge(base, overlay)
+        }
+        (base @ Value::Map(_), Value::Named(_, overlay)) if matches!(*overlay, Value::Map(_)) => {
+            merge(base, *overlay)
+        }
+        (Value::Map(base), Value::Map(overlay)) => {
+            for (key, value) in overlay {
+                match get_mut(base, &key) {
+                    Some(existing) => merge(existing, value),
+                    None => base.push((key, value)),
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// The key names of `value` if it is a map or struct.
+pub(crate) fn keys(value: &Value) -> Vec<String> {
+    fields(value)
+        .map(|map| map.iter().map(|(key, _)| key_name(key)).collect())
+        .unwrap_or_default()
+}
+
+/// Sort the entries of every map inside `value` by their rendered key, so
+/// maps with the same entries render the same whatever their order.
+pub(crate) fn sort_maps(value: &mut Value) {
+    match value {
+        Value::Map(map) => {
+            for (key, value) in map.iter_mut() {
+                sort_maps(key);
+                sort_maps(value);
+            }
+            map.sort_by_cached_key(|(key, _)| key.to_string());
+        }
+        Value::Seq(values) | Value::Tuple(values) => values.iter_mut().for_each(sort_maps),
+        Value::Option(Some(value)) | Value::Named(_, value) => sort_maps(value),
+        _ => {}
+    }
+}
+
+/// Whether `current` has a map key, at any depth, that `file` lacks.
+///
+/// Maps are compared key by key and sequences element by element, recursing
+/// into the values both have.
+pub(crate) fn has_new_keys(file: &Value, current: &Value) -> bool {
+    if let (Some(file), Some(current)) = (fields(file), fields(current)) {
+        return current.iter().any(|(key, current)| {
+            match file
+                .iter()
+                .find_map(|(k, value)| (k == key).then_some(value))
+            {
+                Some(file) => has_new_keys(file, current),
+                None => true,
+            }
+        });
+    }
+    match (file, current) {
+        (Value::Seq(file), Value::Seq(current)) | (Value::Tuple(file), Value::Tuple(current)) => {
+            file.iter()
+                .zip(current)
+                .any(|(file, current)| has_new_keys(file, current))
+        }
+        (Value::Option(Some(file)), Value::Option(Some(current))) => has_new_keys(file, current),
+        (Value::Named(_, file), Value::Named(_, current)) => has_new_keys(file, current),
+        _ => false,
+    }
+}
+
+/// Get a mutable reference to the value stored under `key`.
+pub(crate) fn get_mut<'a>(map: &'a mut [(Value, Value)], key: &Value) -> Option<&'a mut Value> {
+    map.iter_mut()
+        .find_map(|(k, value)| (k == key).then_some(value))
+}
+
+/// The name of a map key, without quotes for plain strings.
+pub(crate) fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(name) => name.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Get a mutable reference to the value at the dotted `path` inside `root`.
+///
+/// Segments index into sequences by position, so `rules.0.allow` works as
+/// well as `0.allow` for a sequence at the root. Struct names and variants
+/// along the way are looked through.
+pub(crate) fn get_path_mut<'a>(root: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.').try_fold(root, |mut current, segment| {
+        while let Value::Named(_, content) = current {
+            current = content;
+        }
+        match current {
+            Value::Map(map) => get_mut(map, &Value::String(segment.to_string())),
+            Value::Seq(seq) | Value::Tuple(seq) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| seq.get_mut(index)),
+            _ => None,
+        }
+    })
+}
+
+/// Store `value` at the dotted `path` inside `root`.
+///
+/// Missing maps along the way are created; sequences are indexed by position
+/// but never grown. Returns `None` if `path` runs through a value that is
+/// neither a map nor a sequence, or past the end of a sequence.
+pub(crate) fn insert(root: &mut Value, path: &str, value: Value) -> Option<()> {
+    let mut current = root;
+    for segment in path.split('.') {
+        while let Value::Named(_, content) = current {
+            current = content;
+        }
+        current = match current {
+            Value::Map(map) => {
+                let key = Value::String(segment.to_string());
+                if get_mut(map, &key).is_none() {
+                    map.push((key.clone(), Value::Map(Vec::new())));
+                }
+                get_mut(map, &key)?
+            }
+            Value::Seq(seq) | Value::Tuple(seq) => seq.get_mut(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    *current = value;
+    Some(())
+}
+
+/// Replace `value` with the number or boolean it holds, if it is a string
+/// holding one. Returns whether it was replaced.
+pub(crate) fn coerce_scalar(value: &mut Value) -> bool {
+    let Value::String(string) = value else {
+        return false;
+    };
+    let trimmed = string.trim();
+    *value = if let Ok(bool) = trimmed.parse::<bool>() {
+        Value::Bool(bool)
+    } else if let Ok(integer) = trimmed.parse::<i64>() {
+        Value::Number(Number::I64(integer))
+    } else if let Ok(integer) = trimmed.parse::<u64>() {
+        Value::Number(Number::U64(integer))
+    } else if let Ok(float) = trimmed.parse::<f64>() {
+        Value::Number(Number::F64(float))
+    } else {
+        return false;
+    };
+    true
+}
+
+/// Look through `Some` and newtypes, which do not show up in the paths
+/// reported by [`serde_path_to_error`].
+fn unwrap_transparent(value: &mut Value) -> &mut Value {
+    let transparent = match value {
+        Value::Option(option) => option.is_some(),
+        Value::Tuple(tuple) => tuple.len() == 1,
+        _ => false,
+    };
+    if !transparent {
+        return value;
+    }
+    match value {
+        Value::Option(Some(inner)) => unwrap_transparent(inner),
+        Value::Tuple(tuple) => unwrap_transparent(&mut tuple[0]),
+        value => value,
+    }
+}
+
+/// Get a mutable reference to the value at `path`, as reported by
+/// [`serde_path_to_error`], inside `root`.
+///
+/// `Some`, newtypes and struct names are looked through, since they do not
+/// show up in `path`.
+pub(crate) fn get_segments_mut<'a>(
+    root: &'a mut Value,
+    path: &serde_path_to_error::Path,
+) -> Option<&'a mut Value> {
+    let mut current = root;
+    for segment in path.iter() {
+        let value = unwrap_transparent(current);
+        current = match segment {
+            Segment::Enum { variant } => match value {
+                Value::Named(name, content) if name == variant => content,
+                Value::Map(map) => get_mut(map, &Value::String(variant.clone()))?,
+                _ => return None,
+            },
+            Segment::Map { key } => get_mut(fields_mut(value)?, &Value::String(key.clone()))?,
+            Segment::Seq { index } => match value {
+                Value::Seq(seq) | Value::Tuple(seq) => seq.get_mut(*index)?,
+                _ => return None,
+            },
+            Segment::Unknown => return None,
+        };
+    }
+    Some(unwrap_transparent(current))
+}
+
+/// Parse an override spec like `network.port=8080;log.level=debug`.
+///
+/// Pairs are separated by `;` outside of double quotes. Values are parsed as
+/// RON and fall back to plain strings.
+pub(crate) fn parse_overrides(spec: &str) -> Result<Vec<(String, Value)>, Error> {
+    let mut pairs = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for c in spec.chars() {
+        match c {
+            ';' if !quoted => {
+                pairs.push(std::mem::take(&mut current));
+                continue;
+            }
+            '"' if !escaped => quoted = !quoted,
+            _ => {}
+        }
+        escaped = quoted && c == '\\' && !escaped;
+        current.push(c);
+    }
+    pairs.push(current);
+
+    pairs
+        .iter()
+        .map(|pair| pair.trim())
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (path, raw) = pair
+                .split_once('=')
+                .ok_or_else(|| Error::InvalidOverride(pair.to_string()))?;
+            let (path, raw) = (path.trim(), raw.trim());
+            if path.is_empty() || path.split('.').any(str::is_empty) {
+                return Err(Error::InvalidOverride(pair.to_string()));
+            }
+            Ok((path.to_string(), parse_scalar(raw)))
+        })
+        .collect()
+}
+
+/// Parse `raw` as a RON value, falling back to a plain string.
+///
+/// Bare identifiers parse as unit variants, which also deserialize into
+/// string fields.
+fn parse_scalar(raw: &str) -> Value {
+    parse::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+/// Collect every scalar inside `value` under its dotted path.
+///
+/// Nested maps add their key names, sequences their indices and enum variants
+/// with content their names to `prefix`. Strings, chars and unit variants are
+/// stored without quotes, other scalars as compact RON.
+/// `None` and empty maps or sequences produce no entry.
+pub(crate) fn flatten(prefix: &str, value: &Value, flat: &mut BTreeMap<String, String>) {
+    let join = |segment: String| {
+        if prefix.is_empty() {
+            segment
+        } else {
+            format!("{}.{}", prefix, segment)
+        }
+    };
+    match value {
+        Value::Map(map) => {
+            for (key, value) in map {
+                flatten(&join(key_name(key)), value, flat);
+            }
+        }
+        Value::Seq(seq) | Value::Tuple(seq) => {
+            for (index, value) in seq.iter().enumerate() {
+                flatten(&join(index.to_string()), value, flat);
+            }
+        }
+        Value::Named(variant, content) => match &**content {
+            Value::Unit => {
+                flat.insert(prefix.to_string(), variant.clone());
+            }
+            Value::Tuple(elements) if elements.len() == 1 => {
+                flatten(&join(variant.clone()), &elements[0], flat);
+            }
+            content => flatten(&join(variant.clone()), content, flat),
+        },
+        Value::Option(Some(value)) => flatten(prefix, value, flat),
+        Value::Option(None) => {}
+        Value::String(string) => {
+            flat.insert(prefix.to_string(), string.clone());
+        }
+        Value::Char(char) => {
+            flat.insert(prefix.to_string(), char.to_string());
+        }
+        scalar => {
+            flat.insert(prefix.to_string(), scalar.to_string());
+        }
+    }
+}
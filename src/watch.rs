@@ -0,0 +1,242 @@
+//! Reload settings when the file changes on disk.
+
+use std::{
+    fmt::Debug,
+    ops::ControlFlow,
+    path::Path,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use log::debug;
+use notify::{EventKind, RecursiveMode, Watcher as _};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{parent_dir, read, Codec, Error, Settings};
+
+/// How long the watcher waits for the file system to settle before acting.
+///
+/// Editors and sync tools often replace a file by deleting and recreating it,
+/// which produces a burst of events. Everything that happens within this
+/// window is reported as a single change.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// The file system events reported by `notify`.
+type Events = Receiver<notify::Result<notify::Event>>;
+
+/// An event emitted by [`Settings::watch`].
+#[derive(Debug)]
+pub enum WatchEvent<T> {
+    /// The settings file changed and was reloaded successfully.
+    Changed(T),
+
+    /// The settings file was removed.
+    ///
+    /// The watcher keeps running and emits [`WatchEvent::Changed`] once a file
+    /// with the same name appears again.
+    Removed,
+
+    /// The settings file changed but could not be reloaded.
+    Error(Error),
+}
+
+/// A handle to a running file watcher.
+///
+/// Watching stops when this handle is dropped.
+pub struct Watcher {
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl Debug for Watcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Watcher").finish_non_exhaustive()
+    }
+}
+
+impl<T> Settings<T>
+where
+    T: Debug + Clone + Serialize + DeserializeOwned + Send + 'static,
+{
+    /// Watch the settings file and call `f` whenever it changes.
+    ///
+    /// The parent directory is watched rather than the file itself, so the
+    /// watcher survives the file being deleted and recreated. While the file
+    /// is absent `f` receives [`WatchEvent::Removed`]; a delete followed
+    /// quickly by a create, as produced by atomic replaces, is reported as a
+    /// single [`WatchEvent::Changed`].
+    ///
+    /// ```rust
+    /// # use settings::{Settings, WatchEvent};
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs, sync::mpsc, thread, time::Duration};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "first")"#)?;
+    /// let settings = Settings::<Config>::load_from(&path)?;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// let _watcher = settings.watch(move |event| tx.send(event).unwrap())?;
+    ///
+    /// fs::remove_file(&path)?;
+    /// assert!(matches!(rx.recv_timeout(Duration::from_secs(5))?, WatchEvent::Removed));
+    ///
+    /// thread::sleep(Duration::from_millis(100));
+    /// fs::write(&path, r#"(foo: "second")"#)?;
+    /// match rx.recv_timeout(Duration::from_secs(5))? {
+    ///     WatchEvent::Changed(config) => assert_eq!(config.foo, "second"),
+    ///     event => panic!("unexpected event {:?}", event),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch<F>(&self, mut f: F) -> Result<Watcher, Error>
+    where
+        F: FnMut(WatchEvent<T>) + Send + 'static,
+    {
+        let (watcher, events) = watch_parent(&self.path)?;
+
+        let path = self.path.clone();
+        let codec = self.codec.clone();
+        let present = path.exists();
+        thread::spawn(move || {
+            run(events, &path, codec.as_ref(), present, |event| {
+                f(event);
+                ControlFlow::Continue(())
+            })
+        });
+
+        Ok(Watcher { _watcher: watcher })
+    }
+
+    /// Watch the settings file and send the reloaded settings to a channel.
+    ///
+    /// Every change sends either the reloaded value or the error that
+    /// prevented reloading it; removing the file sends [`Error::NotFound`].
+    /// If the file cannot be watched at all, that error is sent and nothing
+    /// else. Watching stops, and the returned thread finishes, at the first
+    /// change after the receiver was dropped.
+    ///
+    /// ```rust
+    /// # use settings::Settings;
+    /// # use tempfile::tempdir;
+    /// # use std::{error::Error, fs, time::Duration};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "first")"#)?;
+    /// let settings = Settings::<Config>::load_from(&path)?;
+    ///
+    /// let (handle, rx) = settings.watch_into_channel();
+    /// fs::write(&path, r#"(foo: "second")"#)?;
+    /// assert_eq!(rx.recv_timeout(Duration::from_secs(5))??.foo, "second");
+    ///
+    /// drop(rx);
+    /// fs::write(&path, r#"(foo: "third")"#)?;
+    /// handle.join().unwrap();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch_into_channel(self) -> (JoinHandle<()>, Receiver<Result<T, Error>>) {
+        let (tx, rx) = mpsc::channel();
+        let watched = watch_parent(&self.path);
+        let present = self.path.exists();
+
+        let handle = thread::spawn(move || {
+            let (_watcher, events) = match watched {
+                Ok(watched) => watched,
+                Err(err) => {
+                    let _ = tx.send(Err(err));
+                    return;
+                }
+            };
+            run(events, &self.path, self.codec.as_ref(), present, |event| {
+                let result = match event {
+                    WatchEvent::Changed(value) => Ok(value),
+                    WatchEvent::Removed => Err(Error::NotFound),
+                    WatchEvent::Error(err) => Err(err),
+                };
+                match tx.send(result) {
+                    Ok(()) => ControlFlow::Continue(()),
+                    Err(_) => ControlFlow::Break(()),
+                }
+            })
+        });
+
+        (handle, rx)
+    }
+}
+
+/// Start watching the directory containing `path`.
+fn watch_parent(path: &Path) -> Result<(notify::RecommendedWatcher, Events), Error> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(Error::Watch)?;
+    watcher
+        .watch(parent_dir(path), RecursiveMode::NonRecursive)
+        .map_err(Error::Watch)?;
+    Ok((watcher, rx))
+}
+
+/// Process file system events until the watcher is dropped or `f` breaks.
+fn run<T, F>(events: Events, path: &Path, codec: &dyn Codec<T>, mut present: bool, mut f: F)
+where
+    T: Debug + Clone + Serialize + DeserializeOwned,
+    F: FnMut(WatchEvent<T>) -> ControlFlow<()>,
+{
+    while let Ok(event) = events.recv() {
+        if !concerns(&event, path) {
+            continue;
+        }
+
+        loop {
+            match events.recv_timeout(DEBOUNCE) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        if path.exists() {
+            debug!("Settings file {:?} changed", path);
+            present = true;
+            let flow = match read(path).and_then(|bytes| codec.deserialize(&bytes)) {
+                Ok(value) => f(WatchEvent::Changed(value)),
+                Err(err) => f(WatchEvent::Error(err)),
+            };
+            if flow.is_break() {
+                return;
+            }
+        } else if present {
+            debug!("Settings file {:?} removed", path);
+            present = false;
+            if f(WatchEvent::Removed).is_break() {
+                return;
+            }
+        }
+    }
+}
+
+/// Whether `event` may have changed the file at `path`.
+fn concerns(event: &notify::Result<notify::Event>, path: &Path) -> bool {
+    match event {
+        Ok(event) => {
+            !matches!(event.kind, EventKind::Access(_))
+                && event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == path.file_name())
+        }
+        Err(_) => false,
+    }
+}
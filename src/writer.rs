@@ -0,0 +1,134 @@
+//! Let a single instance own writes to a settings file.
+
+use std::{
+    fs::{File, TryLockError},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use log::debug;
+
+use crate::{lock_path, Error, Settings};
+
+/// Proof that these settings own writes to their file, returned by
+/// [`Settings::try_acquire_writer`].
+///
+/// Ownership is released when the token is dropped.
+#[derive(Debug)]
+#[must_use = "ownership is released when the token is dropped"]
+pub struct WriterToken {
+    _lock: Arc<File>,
+}
+
+impl<T> Settings<T> {
+    /// Try to become the only instance that saves the settings file.
+    ///
+    /// Takes an advisory lock on `{file}.writer` next to the settings file,
+    /// after resolving the path, so every spelling of the same file shares
+    /// the lock. While the returned token is alive, every method writing the file, like
+    /// [`save`](Self::save), [`save_to`](Self::save_to) or
+    /// [`modify_field_locked`](Self::modify_field_locked), fails with
+    /// [`Error::NotWriter`] for every other instance, in this or another
+    /// process, so that a second window cannot clobber the settings of the
+    /// first. Fails with [`Error::NotWriter`] as well if another instance
+    /// already owns the file. As long as nobody owns the file, every
+    /// instance saves as usual.
+    ///
+    /// ```rust
+    /// # use settings::{Error, Settings};
+    /// # use tempfile::tempdir;
+    /// # use std::{error, fs};
+    /// # use serde::{Deserialize, Serialize};
+    /// # #[derive(Debug, Clone, Deserialize, Serialize)]
+    /// # struct Config {
+    /// #     pub foo: String,
+    /// # }
+    /// # fn main() -> Result<(), Box<dyn error::Error>> {
+    /// # let dir = tempdir()?;
+    /// # let path = dir.path().join("settings.ron");
+    /// fs::write(&path, r#"(foo: "")"#)?;
+    /// let mut first = Settings::<Config>::load_from(&path)?;
+    /// let mut second = Settings::<Config>::load_from(&path)?;
+    ///
+    /// let token = first.try_acquire_writer()?;
+    /// assert!(matches!(second.try_acquire_writer(), Err(Error::NotWriter)));
+    /// first.save()?;
+    /// assert!(matches!(second.save(), Err(Error::NotWriter)));
+    /// assert!(matches!(second.save_to(&path), Err(Error::NotWriter)));
+    /// let other_spelling = dir.path().join(".").join("settings.ron");
+    /// assert!(matches!(second.save_to(&other_spelling), Err(Error::NotWriter)));
+    /// first.save_to(&other_spelling)?;
+    /// let result = second.modify_field_locked("foo", |_| {});
+    /// assert!(matches!(result, Err(Error::NotWriter)));
+    ///
+    /// drop(token);
+    /// let _token = second.try_acquire_writer()?;
+    /// second.save()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_acquire_writer(&mut self) -> Result<WriterToken, Error> {
+        let path = self.writer_lock(&self.path)?;
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|source| Error::Lock {
+                source,
+                path: path.clone(),
+            })?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Err(Error::NotWriter),
+            Err(TryLockError::Error(source)) => return Err(Error::Lock { source, path }),
+        }
+
+        debug!("Acquired writer lock {:?}", path);
+        let lock = Arc::new(file);
+        self.writer = Arc::downgrade(&lock);
+        Ok(WriterToken { _lock: lock })
+    }
+
+    /// Fail with [`Error::NotWriter`] if another instance owns the file at
+    /// `path`.
+    pub(crate) fn check_writer(&self, path: &Path) -> Result<(), Error> {
+        let path = self.writer_lock(path)?;
+        if self.writer.strong_count() > 0 && path == self.writer_lock(&self.path)? {
+            return Ok(());
+        }
+
+        let lock_error = |source| Error::Lock {
+            source,
+            path: path.clone(),
+        };
+        let file = match File::options().write(true).open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(lock_error(err)),
+        };
+        match file.try_lock_shared() {
+            Ok(()) => Ok(()),
+            Err(TryLockError::WouldBlock) => Err(Error::NotWriter),
+            Err(TryLockError::Error(source)) => Err(lock_error(source)),
+        }
+    }
+
+    /// The writer lock of the settings file at `path`.
+    ///
+    /// The path is confined and canonicalized first, so every spelling of the
+    /// same file shares one lock.
+    fn writer_lock(&self, path: &Path) -> Result<PathBuf, Error> {
+        let path = self.confined(path)?;
+        let canonical = path.canonicalize().or_else(|_| {
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            let name = path.file_name().ok_or(io::ErrorKind::NotFound)?;
+            parent.canonicalize().map(|parent| parent.join(name))
+        });
+        Ok(lock_path(&canonical.unwrap_or(path), "writer"))
+    }
+}